@@ -0,0 +1,243 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::errors::{Error, ErrorKind, RequestError, TimeoutKind};
+use crate::request::{self, Request, Timeouts};
+use crate::response::Response;
+use crate::transport;
+use crate::wire;
+
+/// Run an I/O operation, failing with a timeout if it takes longer than `timeout` or runs past the deadline
+async fn timed<T>(
+    timeouts: &Timeouts,
+    timeout: Option<Duration>,
+    operation: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    if timeouts.expired() {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "request deadline exceeded"));
+    }
+
+    match timeouts.limit(timeout) {
+        Some(limit) => match tokio::time::timeout(limit, operation).await {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "operation timed out")),
+        },
+        None => operation.await,
+    }
+}
+
+/// Send a request without blocking the executor, following any redirects
+pub(crate) async fn send(request: &Request) -> Result<Response, RequestError> {
+    let timeouts = request.timeouts(None);
+
+    let mut request = request.clone();
+    let mut redirects = Vec::new();
+
+    loop {
+        let mut response = send_retrying(&request, &timeouts).await?;
+
+        match request.redirect(&response, redirects.len())? {
+            Some((next, redirect)) => {
+                redirects.push(redirect);
+                request = next;
+            }
+            None => {
+                response.redirects = redirects;
+                return Ok(response);
+            }
+        }
+    }
+}
+
+/// Send a request without blocking the executor, sending it again whenever its retry policy says to
+async fn send_retrying(request: &Request, timeouts: &Timeouts) -> Result<Response, RequestError> {
+    let mut attempts = 1;
+
+    loop {
+        let result = send_once(request, timeouts).await;
+
+        let delay = request.retry.as_ref().and_then(|policy| policy.delay(attempts, &request.method, &result));
+        match delay {
+            // a body read from a source cannot be sent again, so the result of the last attempt is the one to give back
+            Some(delay) if timeouts.allows(delay) && !request.body.is_spent() => tokio::time::sleep(delay).await,
+            _ => return result,
+        }
+
+        attempts += 1;
+    }
+}
+
+/// Send a request without blocking the executor or following redirects
+///
+/// Plain `http` requests are sent over a tokio `TcpStream`. Connectors are synchronous, so requests that need one (a
+/// custom connector, or TLS) are sent on tokio's blocking thread pool instead, and so are requests sent through a proxy,
+/// requests with a custom (synchronous) resolver and requests whose body is read from a (synchronous) source.
+async fn send_once(request: &Request, timeouts: &Timeouts) -> Result<Response, RequestError> {
+    if request.connector.is_some()
+        || request.scheme != "http"
+        || request.proxy_server().is_some()
+        || request.resolver.is_some()
+        || request.body.as_bytes().is_none()
+    {
+        let request = request.clone();
+        let timeouts = timeouts.clone();
+        return match tokio::task::spawn_blocking(move || request.send_once(&timeouts)).await {
+            Ok(result) => result,
+            Err(error) => Err(RequestError::new(format!("request task failed: {}", error))),
+        };
+    }
+
+    let stream = match connect(request, timeouts).await {
+        Ok(stream) => stream,
+        Err(error) => return Err(timeouts.classify(error)),
+    };
+
+    exchange(request, &mut BufReader::new(stream), timeouts).await
+}
+
+/// Open a TCP connection to the server, trying each of its addresses as `TcpConnector` does
+async fn connect(request: &Request, timeouts: &Timeouts) -> Result<TcpStream, RequestError> {
+    let limit = Some(timeouts.connect_limit());
+
+    let addrs = match timed(timeouts, limit, tokio::net::lookup_host((request.host.as_str(), request.port))).await {
+        Ok(addrs) => transport::interleave(addrs.collect()),
+        Err(error) => return Err(RequestError::io(ErrorKind::Dns, TimeoutKind::Connect, "could not resolve address", error)),
+    };
+
+    if addrs.is_empty() {
+        return Err(RequestError::with_kind(
+            ErrorKind::Dns,
+            format!("could not resolve address: {}", request.host),
+        ));
+    }
+
+    let mut started = Vec::new();
+    let mut failures = Vec::new();
+
+    match timed(timeouts, limit, async { Ok(race(addrs, &mut started, &mut failures).await) }).await {
+        Ok(Some(stream)) => return Ok(stream),
+        Ok(None) => {}
+        Err(error) => {
+            // attempts that were still going when time ran out failed by timing out
+            for &addr in &started {
+                if !failures.iter().any(|(failed, _)| *failed == addr) {
+                    failures.push((addr, io::Error::new(error.kind(), error.to_string())));
+                }
+            }
+        }
+    }
+
+    // the failures came in as the attempts finished, but are reported in the order they were started
+    failures.sort_by_key(|(addr, _)| started.iter().position(|started| started == addr));
+    Err(RequestError::attempts(failures))
+}
+
+/// Connect to whichever of the addresses accepts a connection first, starting a new attempt whenever the last one
+/// fails or has not connected within the attempt delay (RFC 8305), and recording the attempts that start and fail
+async fn race(
+    addrs: Vec<SocketAddr>,
+    started: &mut Vec<SocketAddr>,
+    failures: &mut Vec<(SocketAddr, io::Error)>,
+) -> Option<TcpStream> {
+    // attempts that are still going when one connects are aborted when the set is dropped
+    let mut attempts = tokio::task::JoinSet::new();
+    let mut waiting = addrs.into_iter();
+
+    loop {
+        if let Some(addr) = waiting.next() {
+            attempts.spawn(async move { (addr, TcpStream::connect(addr).await) });
+            started.push(addr);
+        }
+
+        let result = if waiting.len() > 0 {
+            match tokio::time::timeout(transport::ATTEMPT_DELAY, attempts.join_next()).await {
+                Ok(result) => result,
+                Err(_) => continue,
+            }
+        } else {
+            attempts.join_next().await
+        };
+
+        match result {
+            Some(Ok((_, Ok(stream)))) => return Some(stream),
+            Some(Ok((addr, Err(error)))) => failures.push((addr, error)),
+            Some(Err(_)) => {}
+            None => return None,
+        }
+    }
+}
+
+/// Write the request to an open connection and read back the response, like `Request::exchange` does for blocking
+/// connections
+async fn exchange(
+    request: &Request,
+    stream: &mut BufReader<TcpStream>,
+    timeouts: &Timeouts,
+) -> Result<Response, RequestError> {
+    let (serialized, headers) = request.serialize(false, request.decompress);
+
+    // the body is held in memory, so writing it to a buffer cannot block
+    let mut serialized = serialized.into_bytes();
+    if let Err(error) = request.body.write_to(&mut serialized, request::sends_chunked(&headers)) {
+        return Err(timeouts.write_failed(error));
+    }
+
+    let write = async {
+        stream.get_mut().write_all(&serialized).await?;
+        stream.get_mut().flush().await
+    };
+    if let Err(error) = timed(timeouts, timeouts.write, write).await {
+        return Err(timeouts.write_failed(error));
+    }
+
+    // skip over any interim (1xx) responses, such as 100 Continue
+    let (head, parsed) = loop {
+        let mut decoder = wire::HeadDecoder::new();
+
+        while !decoder.is_done() {
+            let input = match timed(timeouts, timeouts.read, stream.fill_buf()).await {
+                Ok(input) => input,
+                Err(error) => return Err(timeouts.read_failed("could not read response", error)),
+            };
+            let consumed = match decoder.decode(input) {
+                Ok(consumed) => consumed,
+                Err(error) => return Err(timeouts.read_failed("could not read response", error)),
+            };
+            stream.consume(consumed);
+        }
+
+        let head = decoder.into_head();
+        if let Some(parsed) = request::final_head(&head)? {
+            break (head, parsed);
+        }
+    };
+
+    let framing = request.framing(&parsed)?;
+
+    let mut decoder = wire::BodyDecoder::new(framing);
+    let mut body = wire::Body::default();
+
+    while !decoder.is_done() {
+        let input = match timed(timeouts, timeouts.read, stream.fill_buf()).await {
+            Ok(input) => input,
+            Err(error) => return Err(timeouts.read_failed("could not read response body", error)),
+        };
+        let consumed = match decoder.decode(input, &mut body.decoded) {
+            Ok(consumed) => consumed,
+            Err(error) => return Err(timeouts.read_failed("could not read response body", error)),
+        };
+        body.raw.extend_from_slice(&input[..consumed]);
+        stream.consume(consumed);
+    }
+
+    body.trailers = decoder.trailers();
+
+    let remote_addr = stream.get_ref().peer_addr().ok();
+    let (response, _) = request.finish(&headers, head, parsed, framing, body, remote_addr)?;
+    Ok(response)
+}
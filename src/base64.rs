@@ -0,0 +1,26 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as standard, padded base64 (RFC 4648, section 4), as used by Basic authentication
+pub(crate) fn encode(input: &[u8]) -> String {
+    let mut encoded = String::with_capacity(input.len().div_ceil(3) * 4);
+
+    for group in input.chunks(3) {
+        let bytes = [group[0], *group.get(1).unwrap_or(&0), *group.get(2).unwrap_or(&0)];
+        let bits = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+
+        for i in 0..4 {
+            if i <= group.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// The value of an `Authorization` (or `Proxy-Authorization`) header for Basic authentication (RFC 7617)
+pub(crate) fn basic_auth(username: &str, password: &str) -> String {
+    format!("Basic {}", encode(format!("{}:{}", username, password).as_bytes()))
+}
@@ -0,0 +1,199 @@
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+/// The size of the chunks a body of unknown length is sent in
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// A source that a body is read from as it is sent, which can only be read once
+type Source = Arc<Mutex<Option<Box<dyn Read + Send>>>>;
+
+#[derive(Clone)]
+enum Kind {
+    Bytes(Vec<u8>),
+    Reader { source: Source, length: Option<u64> },
+}
+
+/// The body of a request: text, raw bytes, or a source that is read from as the request is sent
+///
+/// A body with a known length is sent with a `Content-Length` header. A source of unknown length is sent with
+/// `Transfer-Encoding: chunked`, so it never has to be held in memory.
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::RequestBody;
+///
+/// let text = RequestBody::from("name=John");
+/// let bytes = RequestBody::from(vec![0u8, 159, 146, 150]);
+/// let file = RequestBody::reader(std::io::empty(), None);
+///
+/// assert_eq!(text.len(), Some(9));
+/// assert_eq!(bytes.as_bytes(), Some(&[0u8, 159, 146, 150][..]));
+/// assert_eq!(file.len(), None);
+/// ```
+#[derive(Clone)]
+pub struct RequestBody {
+    kind: Kind,
+}
+
+impl RequestBody {
+    /// An empty body
+    pub fn empty() -> RequestBody {
+        RequestBody::from(Vec::new())
+    }
+
+    /// A body read from `reader` as the request is sent, which is `length` bytes long if the length is known
+    ///
+    /// The reader is only read once, so a request with this body cannot be sent again (for example, to follow a 307
+    /// redirect, or to retry after the body was sent).
+    pub fn reader<R: Read + Send + 'static>(reader: R, length: Option<u64>) -> RequestBody {
+        RequestBody {
+            kind: Kind::Reader {
+                source: Arc::new(Mutex::new(Some(Box::new(reader)))),
+                length,
+            },
+        }
+    }
+
+    /// The length of the body in bytes, if it is known
+    pub fn len(&self) -> Option<u64> {
+        match &self.kind {
+            Kind::Bytes(bytes) => Some(bytes.len() as u64),
+            Kind::Reader { length, .. } => *length,
+        }
+    }
+
+    /// Whether the body is known to be empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// The body, if it is held in memory rather than read from a source
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.kind {
+            Kind::Bytes(bytes) => Some(bytes),
+            Kind::Reader { .. } => None,
+        }
+    }
+
+    /// Whether the body is read from a source that has already been used up by sending it
+    pub(crate) fn is_spent(&self) -> bool {
+        match &self.kind {
+            Kind::Bytes(_) => false,
+            Kind::Reader { source, .. } => source.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).is_none(),
+        }
+    }
+
+    /// A reader over the body, taking the source out of a body that is read from one
+    pub(crate) fn into_reader(self) -> Box<dyn Read + Send> {
+        match self.kind {
+            Kind::Bytes(bytes) => Box::new(io::Cursor::new(bytes)),
+            Kind::Reader { source, .. } => {
+                let taken = source.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+                taken.unwrap_or_else(|| Box::new(Spent))
+            }
+        }
+    }
+
+    /// Write the body to a connection, in chunked transfer coding if `chunked` is set (RFC 9112, section 7.1)
+    pub(crate) fn write_to<W: Write>(&self, out: &mut W, chunked: bool) -> io::Result<()> {
+        let (source, length) = match &self.kind {
+            Kind::Bytes(bytes) if chunked => return write_chunked(&mut &bytes[..], out),
+            Kind::Bytes(bytes) => return out.write_all(bytes),
+            Kind::Reader { source, length } => (source, *length),
+        };
+
+        let taken = source.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        let mut reader = match taken {
+            Some(reader) => reader,
+            None => return Err(spent()),
+        };
+
+        if chunked {
+            return write_chunked(&mut reader, out);
+        }
+
+        let copied = io::copy(&mut reader.by_ref().take(length.unwrap_or(u64::MAX)), out)?;
+        match length {
+            Some(length) if copied < length => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("body source ended after {} of {} bytes", copied, length),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn spent() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "a body read from a source can only be sent once")
+}
+
+/// Stands in for a source that has already been read, failing every read
+struct Spent;
+
+impl Read for Spent {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(spent())
+    }
+}
+
+/// Write everything `reader` gives as chunks, followed by the last chunk
+fn write_chunked<R: Read + ?Sized, W: Write>(reader: &mut R, out: &mut W) -> io::Result<()> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        out.write_all(format!("{:x}\r\n", read).as_bytes())?;
+        out.write_all(&buffer[..read])?;
+        out.write_all(b"\r\n")?;
+    }
+
+    out.write_all(b"0\r\n\r\n")
+}
+
+impl Default for RequestBody {
+    fn default() -> RequestBody {
+        RequestBody::empty()
+    }
+}
+
+impl core::fmt::Debug for RequestBody {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match &self.kind {
+            Kind::Bytes(bytes) => write!(f, "{:?}", String::from_utf8_lossy(bytes)),
+            Kind::Reader { length: Some(length), .. } => write!(f, "<reader of {} bytes>", length),
+            Kind::Reader { length: None, .. } => write!(f, "<reader>"),
+        }
+    }
+}
+
+impl From<Vec<u8>> for RequestBody {
+    fn from(bytes: Vec<u8>) -> RequestBody {
+        RequestBody {
+            kind: Kind::Bytes(bytes),
+        }
+    }
+}
+
+impl From<&[u8]> for RequestBody {
+    fn from(bytes: &[u8]) -> RequestBody {
+        RequestBody::from(bytes.to_vec())
+    }
+}
+
+impl From<String> for RequestBody {
+    fn from(text: String) -> RequestBody {
+        RequestBody::from(text.into_bytes())
+    }
+}
+
+impl From<&str> for RequestBody {
+    fn from(text: &str) -> RequestBody {
+        RequestBody::from(text.as_bytes())
+    }
+}
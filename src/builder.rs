@@ -1,7 +1,21 @@
-use std::collections::HashMap;
-
-use crate::errors::{BuilderError, Error};
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::base64;
+use crate::body::RequestBody;
+use crate::errors::{BuilderError, Error, ErrorKind};
+use crate::headers::HeaderMap;
+use crate::json::Json;
+use crate::multipart::Multipart;
+use crate::proxy::Proxy;
+use crate::resolver::Resolver;
 use crate::request::Request;
+use crate::retry::RetryPolicy;
+use crate::url::{encode_form, encode_query, percent_decode, Url};
+use crate::transport::{Connector, TlsConnector};
+#[cfg(unix)]
+use crate::transport::UnixConnector;
 
 /// Describes the request builder
 pub trait ValidBuilder {
@@ -32,8 +46,77 @@ pub trait ValidBuilder {
     /// Set the body of the request (example: {"name": "John"})
     fn body(&mut self, body: String) -> &mut Self;
 
+    /// Set the body of the request to raw bytes (example: the contents of an image)
+    fn body_bytes(&mut self, body: Vec<u8>) -> &mut Self;
+
+    /// Set the body of the request to be read from `reader` as it is sent, which is `length` bytes long if that is
+    /// known (example: an open `File`, with its length from its metadata)
+    fn body_reader<R: Read + Send + 'static>(&mut self, reader: R, length: Option<u64>) -> &mut Self;
+
+    /// Set the body of the request to a `multipart/form-data` form, and the `Content-Type` header to match
+    fn multipart(&mut self, form: Multipart) -> &mut Self;
+
+    /// Set the body of the request to a JSON value, and the `Content-Type` header to match (example: `{"name": "John"}`)
+    fn json(&mut self, value: Json) -> &mut Self;
+
+    /// Add a query parameter, percent-encoded and appended to the path when the request is built (example: q, rust lang)
+    fn query(&mut self, key: String, value: String) -> &mut Self;
+
+    /// Set the body of the request to a URL-encoded form, and the `Content-Type` header to match (example: name, John)
+    fn form(&mut self, pairs: Vec<(String, String)>) -> &mut Self;
+
     /// Set the URI of the request (example: http://httpbin.org/post?name=bob)
+    ///
+    /// An invalid URI is reported as an error by `build`.
     fn uri(&mut self, uri: String) -> &mut Self;
+
+    /// Set the scheme, host, port and path of the request from a parsed URL
+    fn url(&mut self, url: Url) -> &mut Self;
+
+    /// Set the scheme of the request (example: https)
+    fn scheme(&mut self, scheme: String) -> &mut Self;
+
+    /// Set the connector used to open the connection to the server (example: a `TcpConnector` with a longer timeout)
+    fn connector(&mut self, connector: Arc<dyn Connector>) -> &mut Self;
+
+    /// Send the request over the Unix domain socket at `path`, rather than a TCP connection to its host and port
+    /// (example: /var/run/docker.sock)
+    #[cfg(unix)]
+    fn unix_socket(&mut self, path: String) -> &mut Self;
+
+    /// Set the TLS connector used to secure `https` connections (example: a `RustlsConnector` trusting a private CA)
+    fn tls_connector(&mut self, tls_connector: Arc<dyn TlsConnector>) -> &mut Self;
+
+    /// Set how long to wait for the connection to be established, including any TLS handshake (example: 5 seconds)
+    fn connect_timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Set how long to wait for the server to send anything before giving up (example: 30 seconds)
+    fn read_timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Set how long a write of the request may block before giving up (example: 30 seconds)
+    fn write_timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Set a deadline for the whole request, from connecting to reading the last byte of the body (example: 60 seconds)
+    fn timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Set the most redirects to follow before giving up, or `0` to return redirects as they are (example: 10)
+    fn max_redirects(&mut self, max_redirects: usize) -> &mut Self;
+
+    /// Set whether to ask for a compressed response and decompress it (example: false, to get the body as it was sent)
+    fn decompress(&mut self, decompress: bool) -> &mut Self;
+
+    /// Set the policy for retrying the request when it fails (example: `RetryPolicy::new()`, for the default policy)
+    fn retry(&mut self, policy: RetryPolicy) -> &mut Self;
+
+    /// Set the proxies to send the request through (example: `Proxy::new("http://proxy.example.com:3128")`)
+    fn proxy(&mut self, proxy: Proxy) -> &mut Self;
+
+    /// Send the request through the proxies set in the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment variables,
+    /// if there are any
+    fn proxy_from_env(&mut self) -> &mut Self;
+
+    /// Set the resolver that looks up the addresses of the server (example: a `StaticResolver` pinning the host to an IP)
+    fn resolver(&mut self, resolver: Arc<dyn Resolver>) -> &mut Self;
 }
 
 /// The request builder
@@ -54,29 +137,113 @@ pub struct Builder {
     /// The path of the request (example: `/post`)
     pub path: Option<String>,
 
+    /// The query parameters added to the path, unencoded (example: `q`, `rust lang`)
+    pub query: Vec<(String, String)>,
+
     /// The headers of the request (example: `Content-Type: application/json`)
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
 
     /// The body of the request (example: `{"name": "John"}`)
-    pub body: Option<String>,
+    pub body: Option<RequestBody>,
+
+    /// The scheme of the request (example: `https`)
+    pub scheme: Option<String>,
+
+    /// The connector used to open the connection (a plain TCP connection if not set)
+    pub connector: Option<Arc<dyn Connector>>,
+
+    /// The TLS connector used for `https` requests (`rustls`, with the `tls` feature, if not set)
+    pub tls_connector: Option<Arc<dyn TlsConnector>>,
+
+    /// How long to wait for the connection to be established (`5` seconds if not set)
+    pub connect_timeout: Option<Duration>,
+
+    /// How long to wait for the server to send anything (no limit if not set)
+    pub read_timeout: Option<Duration>,
+
+    /// How long a write of the request may block (no limit if not set)
+    pub write_timeout: Option<Duration>,
+
+    /// The deadline for the whole request (no limit if not set)
+    pub timeout: Option<Duration>,
+
+    /// The most redirects to follow before giving up, or `0` to return redirects as they are (example: `10`)
+    pub max_redirects: usize,
+
+    /// Whether to ask for a compressed response (gzip or deflate) and decompress it (example: `true`)
+    pub decompress: bool,
+
+    /// When to send the request again if it fails (it is only sent once if not set)
+    pub retry: Option<RetryPolicy>,
+
+    /// The proxies to send the request through (a direct connection if not set)
+    pub proxy: Option<Proxy>,
+
+    /// The resolver that looks up the addresses of the server (the system resolver if not set)
+    pub resolver: Option<Arc<dyn Resolver>>,
+
+    /// The error from an invalid URI, returned by `build`
+    error: Option<BuilderError>,
+
+    /// The error from an invalid multipart form, returned by `build` unless the body is replaced
+    body_error: Option<BuilderError>,
+
+    /// The decoded username and password from the URL, sent with Basic authentication unless an `Authorization`
+    /// header is set (they are only for the host of that URL, so setting another one drops them)
+    credentials: Option<(String, String)>,
+}
+
+impl Builder {
+    /// The path, with the query parameters added to any query it already has
+    fn path_with_query(&self) -> String {
+        let mut path = self.path.clone().unwrap_or_default();
+        if self.query.is_empty() {
+            return path;
+        }
+
+        if !path.contains('?') {
+            path.push('?');
+        } else if !path.ends_with('?') && !path.ends_with('&') {
+            path.push('&');
+        }
+        path.push_str(&encode_query(&self.query));
+        path
+    }
 }
 
 impl ValidBuilder for Builder {
     fn new() -> Builder {
-        let mut headers = HashMap::new();
-        headers.insert("Connection".to_string(), "close".to_string());
         Builder {
             host: None,
             port: Some(80),
             http_version: Some("1.1".to_string()),
             method: Some("GET".to_string()),
             path: None,
-            headers: headers,
+            query: Vec::new(),
+            headers: HeaderMap::new(),
             body: None,
+            scheme: Some("http".to_string()),
+            connector: None,
+            tls_connector: None,
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            timeout: None,
+            max_redirects: 10,
+            decompress: true,
+            retry: None,
+            proxy: None,
+            resolver: None,
+            error: None,
+            body_error: None,
+            credentials: None,
         }
     }
 
     fn build(&self) -> Result<Request, BuilderError> {
+        if let Some(error) = self.error.as_ref().or(self.body_error.as_ref()) {
+            return Err(error.clone());
+        }
         if self.host.is_none() {
             return Err(BuilderError::new("host is required".to_string()));
         }
@@ -86,21 +253,40 @@ impl ValidBuilder for Builder {
         if self.path.is_none() {
             return Err(BuilderError::new("path is required".to_string()));
         }
-        if self.headers.is_empty() {
-            return Err(BuilderError::new("headers are required".to_string()));
+
+        let mut headers = self.headers.clone();
+        if let Some((username, password)) = &self.credentials {
+            if headers.get("Authorization").is_none() {
+                headers.insert("Authorization".to_string(), base64::basic_auth(username, password));
+            }
         }
+
         Ok(Request {
             host: self.host.clone().unwrap(),
             port: self.port.unwrap(),
             http_version: self.http_version.clone().unwrap(),
             method: self.method.clone().unwrap(),
-            path: self.path.clone().unwrap(),
-            headers: self.headers.clone(),
-            body: self.body.clone().unwrap_or("".to_string()),
+            path: self.path_with_query(),
+            headers,
+            body: self.body.clone().unwrap_or_default(),
+            scheme: self.scheme.clone().unwrap_or("http".to_string()),
+            connector: self.connector.clone(),
+            tls_connector: self.tls_connector.clone(),
+            connect_timeout: self.connect_timeout,
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            timeout: self.timeout,
+            max_redirects: self.max_redirects,
+            decompress: self.decompress,
+            retry: self.retry.clone(),
+            proxy: self.proxy.clone(),
+            resolver: self.resolver.clone(),
         })
     }
 
     fn host(&mut self, host: String) -> &mut Self {
+        // credentials from a URL were for the host it named, and are not sent to any other
+        self.credentials = None;
         self.host = Some(host);
         self
     }
@@ -131,52 +317,171 @@ impl ValidBuilder for Builder {
     }
 
     fn body(&mut self, body: String) -> &mut Self {
-        self.body = Some(body);
+        self.body_error = None;
+        self.body = Some(RequestBody::from(body));
         self
     }
 
-    // valid uri formats:
-    // http://example.com
-    // http://example.com/
-    // http://example.com/path
-    // http://example.com/path:80
-    fn uri(&mut self, uri: String) -> &mut Self {
-        let uri = uri.trim_start_matches("http://").to_string();
-        let parts: Vec<&str> = uri.splitn(2, '/').collect();
-
-        if parts.len() == 1 {
-            let host = parts[0].to_string();
-            self.host(host);
-            self.path("/".to_string());
-            return self;
-        }
+    fn body_bytes(&mut self, body: Vec<u8>) -> &mut Self {
+        self.body_error = None;
+        self.body = Some(RequestBody::from(body));
+        self
+    }
 
-        let host = parts[0].to_string();
-        self.host(host);
-        if parts.len() > 1 {
-            let path = parts[1].to_string();
+    fn body_reader<R: Read + Send + 'static>(&mut self, reader: R, length: Option<u64>) -> &mut Self {
+        self.body_error = None;
+        self.body = Some(RequestBody::reader(reader, length));
+        self
+    }
 
-            if parts.contains(&":") {
-                let path_parts: Vec<&str> = path.splitn(2, ':').collect();
-                let path = path_parts[0].to_string();
-                let port = path_parts[1].parse::<u16>().unwrap();
-                self.port(port);
+    fn query(&mut self, key: String, value: String) -> &mut Self {
+        self.query.push((key, value));
+        self
+    }
 
-                if path.starts_with("/") {
-                    self.path(path);
-                } else {
-                    self.path("/".to_string() + &path);
-                }
+    fn form(&mut self, pairs: Vec<(String, String)>) -> &mut Self {
+        self.headers
+            .insert("Content-Type".to_string(), "application/x-www-form-urlencoded".to_string());
+        self.body_error = None;
+        self.body = Some(RequestBody::from(encode_form(&pairs)));
+        self
+    }
 
+    fn multipart(&mut self, form: Multipart) -> &mut Self {
+        self.headers.insert("Content-Type".to_string(), form.content_type());
+        self.body_error = form.error().cloned();
+        self.body = Some(form.into_body());
+        self
+    }
+
+    fn json(&mut self, value: Json) -> &mut Self {
+        self.headers
+            .insert("Content-Type".to_string(), "application/json".to_string());
+        self.body_error = None;
+        self.body = Some(RequestBody::from(value.to_string()));
+        self
+    }
+
+    // a URI without a scheme (example: httpbin.org/ip) is taken to be http, even if a URL appears later in its path or
+    // query (example: example.com/r?to=http://x)
+    fn uri(&mut self, uri: String) -> &mut Self {
+        let has_scheme = uri.find("://").is_some_and(|end| !uri[..end].contains(['/', '?', '#']));
+        let uri = if has_scheme {
+            uri
+        } else {
+            format!("http://{}", uri)
+        };
+
+        match Url::parse(&uri) {
+            Ok(url) => self.url(url),
+            Err(error) => {
+                self.error = Some(error);
+                self
+            }
+        }
+    }
+
+    fn url(&mut self, url: Url) -> &mut Self {
+        let host = match url.host() {
+            Some(host) if !host.is_empty() => host.to_string(),
+            _ => {
+                self.error = Some(BuilderError::with_kind(ErrorKind::InvalidUrl, format!("URL has no host: {}", url)));
                 return self;
-            } else {
-                if path.starts_with("/") {
-                    self.path(path);
-                } else {
-                    self.path("/".to_string() + &path);
-                }
             }
+        };
+        let port = match url.port_or_default() {
+            Some(port) => port,
+            None => {
+                self.error = Some(BuilderError::with_kind(ErrorKind::InvalidUrl, format!(
+                    "URL has no port, and {} has no default: {}",
+                    url.scheme(),
+                    url
+                )));
+                return self;
+            }
+        };
+
+        self.error = None;
+        self.scheme(url.scheme().to_string());
+        self.host(host);
+        self.port(port);
+        self.path(url.request_target());
+
+        // credentials in the URL are sent with Basic authentication, to this URL's host only
+        if !url.username().is_empty() || url.password().is_some() {
+            self.credentials = Some((percent_decode(url.username()), percent_decode(url.password().unwrap_or(""))));
         }
         self
     }
+
+    fn scheme(&mut self, scheme: String) -> &mut Self {
+        self.scheme = Some(scheme);
+        self
+    }
+
+    fn connector(&mut self, connector: Arc<dyn Connector>) -> &mut Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    #[cfg(unix)]
+    fn unix_socket(&mut self, path: String) -> &mut Self {
+        self.connector = Some(Arc::new(UnixConnector::new(path)));
+        self
+    }
+
+    fn tls_connector(&mut self, tls_connector: Arc<dyn TlsConnector>) -> &mut Self {
+        self.tls_connector = Some(tls_connector);
+        self
+    }
+
+    fn connect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    fn read_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    fn write_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn max_redirects(&mut self, max_redirects: usize) -> &mut Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    fn decompress(&mut self, decompress: bool) -> &mut Self {
+        self.decompress = decompress;
+        self
+    }
+
+    fn retry(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    fn proxy(&mut self, proxy: Proxy) -> &mut Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    fn proxy_from_env(&mut self) -> &mut Self {
+        self.proxy = Proxy::from_env();
+        self
+    }
+
+    fn resolver(&mut self, resolver: Arc<dyn Resolver>) -> &mut Self {
+        self.resolver = Some(resolver);
+        self
+    }
 }
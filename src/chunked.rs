@@ -0,0 +1,163 @@
+use crate::headers::HeaderMap;
+
+/// The longest chunk-size or trailer line accepted before the body is rejected
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// Where the decoder is within the chunked body
+#[derive(Clone, Debug, PartialEq)]
+enum State {
+    /// Reading a chunk-size line, including any chunk extensions
+    Size,
+    /// Reading the data of a chunk, with the given number of bytes left
+    Data(u64),
+    /// Reading the CRLF that ends the data of a chunk
+    DataEnd,
+    /// Reading the trailer section that follows the last chunk
+    Trailers,
+    /// The whole body has been decoded
+    Done,
+}
+
+/// Decodes a body sent with `Transfer-Encoding: chunked` (RFC 9112, section 7.1)
+///
+/// The decoder does not do any I/O itself: bytes are fed to it with [`ChunkedDecoder::decode`] as they arrive,
+/// so the same decoder can be used on a buffered response or on a live connection.
+#[derive(Clone, Debug)]
+pub(crate) struct ChunkedDecoder {
+    state: State,
+    line: Vec<u8>,
+    trailers: HeaderMap,
+}
+
+impl ChunkedDecoder {
+    pub(crate) fn new() -> ChunkedDecoder {
+        ChunkedDecoder {
+            state: State::Size,
+            line: Vec::new(),
+            trailers: HeaderMap::new(),
+        }
+    }
+
+    /// Whether the last chunk and the trailer section have been read
+    pub(crate) fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// The trailer fields sent after the last chunk
+    pub(crate) fn trailers(&self) -> &HeaderMap {
+        &self.trailers
+    }
+
+    /// Decode as much of `input` as possible, appending the payload to `out`
+    ///
+    /// Returns the number of bytes of `input` that were consumed. Anything after the end of the body is left unconsumed.
+    pub(crate) fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<usize, String> {
+        let mut pos = 0;
+
+        while pos < input.len() {
+            match self.state {
+                State::Size => {
+                    if let Some(line) = self.take_line(input, &mut pos)? {
+                        let size = parse_size_line(&line)?;
+                        self.state = if size == 0 {
+                            State::Trailers
+                        } else {
+                            State::Data(size)
+                        };
+                    }
+                }
+                State::Data(remaining) => {
+                    let available = (input.len() - pos) as u64;
+                    let take = remaining.min(available) as usize;
+                    out.extend_from_slice(&input[pos..pos + take]);
+                    pos += take;
+
+                    let remaining = remaining - take as u64;
+                    self.state = if remaining == 0 {
+                        State::DataEnd
+                    } else {
+                        State::Data(remaining)
+                    };
+                }
+                State::DataEnd => {
+                    if let Some(line) = self.take_line(input, &mut pos)? {
+                        if !line.is_empty() {
+                            return Err("chunk data is not followed by CRLF".to_string());
+                        }
+                        self.state = State::Size;
+                    }
+                }
+                State::Trailers => {
+                    if let Some(line) = self.take_line(input, &mut pos)? {
+                        if line.is_empty() {
+                            self.state = State::Done;
+                        } else {
+                            self.parse_trailer(&line)?;
+                        }
+                    }
+                }
+                State::Done => break,
+            }
+        }
+
+        Ok(pos)
+    }
+
+    /// Take a full line from `input`, buffering partial lines between calls
+    ///
+    /// Returns `None` if the line is not complete yet. The line ending is stripped; a bare LF is accepted as well as CRLF.
+    fn take_line(&mut self, input: &[u8], pos: &mut usize) -> Result<Option<Vec<u8>>, String> {
+        let rest = &input[*pos..];
+
+        match rest.iter().position(|&byte| byte == b'\n') {
+            Some(end) => {
+                self.line.extend_from_slice(&rest[..end]);
+                *pos += end + 1;
+
+                if self.line.last() == Some(&b'\r') {
+                    self.line.pop();
+                }
+                if self.line.len() > MAX_LINE_LENGTH {
+                    return Err("chunked body line is too long".to_string());
+                }
+
+                Ok(Some(std::mem::take(&mut self.line)))
+            }
+            None => {
+                self.line.extend_from_slice(rest);
+                *pos = input.len();
+
+                if self.line.len() > MAX_LINE_LENGTH {
+                    return Err("chunked body line is too long".to_string());
+                }
+
+                Ok(None)
+            }
+        }
+    }
+
+    fn parse_trailer(&mut self, line: &[u8]) -> Result<(), String> {
+        let line = String::from_utf8_lossy(line);
+
+        let (key, value) = match line.split_once(':') {
+            Some(parts) => parts,
+            None => return Err(format!("invalid trailer field: {}", line)),
+        };
+
+        self.trailers
+            .append(key.trim().to_string(), value.trim().to_string());
+        Ok(())
+    }
+}
+
+/// Parse a chunk-size line, ignoring any chunk extensions (`;name=value`)
+fn parse_size_line(line: &[u8]) -> Result<u64, String> {
+    let line = String::from_utf8_lossy(line);
+    let size = line.split(';').next().unwrap_or("").trim();
+
+    if size.is_empty() || !size.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid chunk size: {}", line));
+    }
+
+    u64::from_str_radix(size, 16).map_err(|_| format!("chunk size is too large: {}", size))
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cookies::CookieJar;
+use crate::errors::RequestError;
+use crate::request::{Request, Timeouts};
+use crate::resolver::Resolver;
+use crate::response::Response;
+use crate::retry::RetryPolicy;
+use crate::transport::{Connector, Stream, TlsConnector};
+use crate::url::Url;
+
+/// Describes a client, which sends requests over a pool of reusable connections
+pub trait ValidClient {
+    /// Create a new client, with an empty connection pool
+    fn new() -> Self;
+
+    /// Send a request, reusing an idle connection to the same host and port if there is one
+    fn send(&self, request: &Request) -> Result<Response, RequestError>;
+
+    /// Set the most idle connections kept open to each host and port (example: 8)
+    fn max_idle_per_host(&mut self, max_idle_per_host: usize) -> &mut Self;
+
+    /// Set how long a connection may sit idle in the pool before it is closed (example: 90 seconds)
+    fn idle_timeout(&mut self, idle_timeout: Duration) -> &mut Self;
+
+    /// Set the connect timeout for requests that do not set their own (example: 5 seconds)
+    fn connect_timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Set the read timeout for requests that do not set their own (example: 30 seconds)
+    fn read_timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Set the write timeout for requests that do not set their own (example: 30 seconds)
+    fn write_timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Set the deadline for requests that do not set their own (example: 60 seconds)
+    fn timeout(&mut self, timeout: Duration) -> &mut Self;
+
+    /// Set the cookie jar that stores the cookies servers set, and sends them back with later requests
+    fn cookie_jar(&mut self, cookie_jar: CookieJar) -> &mut Self;
+
+    /// Set the retry policy for requests that do not set their own (example: `RetryPolicy::new()`)
+    fn retry(&mut self, policy: RetryPolicy) -> &mut Self;
+
+    /// Set the resolver for requests that do not set their own (example: a `CachingResolver`, to share its cache)
+    fn resolver(&mut self, resolver: Arc<dyn Resolver>) -> &mut Self;
+}
+
+/// The idle connections, by what they were opened to and how
+type Pool = HashMap<Key, Vec<Idle>>;
+
+/// What a pooled connection was opened to, and how, which a request must match to reuse it
+///
+/// Two requests to the same scheme, host and port can still need different connections: one sent through a proxy, or
+/// over a custom connector (such as a Unix socket), must not reuse a connection opened directly, and the other way
+/// round. Nor must one pinned to an address by its resolver, or secured by another TLS connector.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Key {
+    scheme: String,
+    host: String,
+    port: u16,
+    /// The proxy the connection goes through, if any
+    proxy: Option<String>,
+    connector: Identity<dyn Connector>,
+    tls_connector: Identity<dyn TlsConnector>,
+    resolver: Identity<dyn Resolver>,
+}
+
+impl Key {
+    fn new(request: &Request) -> Key {
+        Key {
+            scheme: request.scheme.clone(),
+            host: request.host.to_ascii_lowercase(),
+            port: request.port,
+            proxy: request.proxy_server().map(|server| server.to_string()),
+            connector: Identity(request.connector.clone()),
+            tls_connector: Identity(request.tls_connector.clone()),
+            resolver: Identity(request.resolver.clone()),
+        }
+    }
+}
+
+/// A shared value compared by which value it is, rather than by what it holds, for the parts of a request that are
+/// trait objects
+///
+/// The value is kept alive along with the key, so its address cannot be reused by another value in the meantime.
+#[derive(Debug)]
+struct Identity<T: ?Sized>(Option<Arc<T>>);
+
+impl<T: ?Sized> Identity<T> {
+    fn address(&self) -> Option<*const ()> {
+        self.0.as_ref().map(|value| Arc::as_ptr(value) as *const ())
+    }
+}
+
+impl<T: ?Sized> PartialEq for Identity<T> {
+    fn eq(&self, other: &Identity<T>) -> bool {
+        self.address() == other.address()
+    }
+}
+
+impl<T: ?Sized> Eq for Identity<T> {}
+
+impl<T: ?Sized> std::hash::Hash for Identity<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.address().hash(state);
+    }
+}
+
+/// A connection sitting in the pool, waiting to be reused
+struct Idle {
+    stream: BufReader<Box<dyn Stream>>,
+    since: Instant,
+}
+
+impl core::fmt::Debug for Idle {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Idle").field("since", &self.since).finish()
+    }
+}
+
+impl Idle {
+    /// Whether the connection can still be used: it has not been idle for too long, and the server has not closed it
+    /// or sent anything unexpected in the meantime
+    fn is_usable(&self, idle_timeout: Duration) -> bool {
+        self.since.elapsed() < idle_timeout && self.stream.buffer().is_empty() && self.stream.get_ref().is_idle()
+    }
+}
+
+/// The client type, which keeps a pool of idle keep-alive connections for each scheme, host and port (and the proxy,
+/// connectors and resolver they were opened with)
+///
+/// Clones of a client share the same pool, so a client can be cloned cheaply and sent to other threads.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The most idle connections kept open to each host and port (example: `8`)
+    pub max_idle_per_host: usize,
+
+    /// How long a connection may sit idle before it is closed (example: `90` seconds)
+    pub idle_timeout: Duration,
+
+    /// How long to wait for a new connection to be established, for requests that do not set their own
+    pub connect_timeout: Option<Duration>,
+
+    /// How long to wait for the server to send anything, for requests that do not set their own
+    pub read_timeout: Option<Duration>,
+
+    /// How long a write of a request may block, for requests that do not set their own
+    pub write_timeout: Option<Duration>,
+
+    /// The deadline for each whole request, for requests that do not set their own
+    pub timeout: Option<Duration>,
+
+    /// The cookie jar that cookies are stored in and sent from (no cookies are kept if not set)
+    pub cookie_jar: Option<CookieJar>,
+
+    /// When to send a request again if it fails, for requests that do not set their own
+    pub retry: Option<RetryPolicy>,
+
+    /// The resolver that looks up the addresses of servers, for requests that do not set their own
+    pub resolver: Option<Arc<dyn Resolver>>,
+
+    pool: Arc<Mutex<Pool>>,
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::new()
+    }
+}
+
+impl Client {
+    /// Take a usable idle connection to `key` out of the pool, closing any stale ones found on the way
+    fn checkout(&self, key: &Key) -> Option<BufReader<Box<dyn Stream>>> {
+        let mut pool = self.pool.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let idle = pool.get_mut(key)?;
+
+        // the most recently used connections are at the end, and are the least likely to have been closed
+        while let Some(connection) = idle.pop() {
+            if connection.is_usable(self.idle_timeout) {
+                return Some(connection.stream);
+            }
+        }
+
+        None
+    }
+
+    /// Put a connection back into the pool once a response has been read from it
+    fn checkin(&self, key: Key, stream: BufReader<Box<dyn Stream>>) {
+        if self.max_idle_per_host == 0 {
+            return;
+        }
+
+        let mut pool = self.pool.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // evict connections that have gone stale, for every host
+        pool.retain(|_, idle| {
+            idle.retain(|connection| connection.since.elapsed() < self.idle_timeout);
+            !idle.is_empty()
+        });
+
+        let idle = pool.entry(key).or_default();
+        if idle.len() >= self.max_idle_per_host {
+            idle.remove(0);
+        }
+
+        idle.push(Idle {
+            stream,
+            since: Instant::now(),
+        });
+    }
+}
+
+impl Client {
+    /// A copy of the request with the cookies from the jar for `url` added, if there are any
+    fn with_cookies(&self, request: &Request, url: &Url) -> Option<Request> {
+        let cookies = self.cookie_jar.as_ref()?.cookie_header(url)?;
+
+        let mut request = request.clone();
+        let cookies = match request.headers.get("Cookie") {
+            Some(existing) => format!("{}; {}", existing, cookies),
+            None => cookies,
+        };
+        request.headers.insert("Cookie".to_string(), cookies);
+
+        Some(request)
+    }
+
+    /// A copy of the request using the client's resolver, if it has one and the request does not have its own
+    fn with_resolver(&self, request: &Request) -> Option<Request> {
+        let resolver = self.resolver.as_ref().filter(|_| request.resolver.is_none())?;
+
+        let mut request = request.clone();
+        request.resolver = Some(resolver.clone());
+
+        Some(request)
+    }
+
+    /// Send a request over a pooled connection if there is one, without following redirects
+    fn send_once(&self, request: &Request, timeouts: &Timeouts) -> Result<Response, RequestError> {
+        // the URL is only needed for cookies, and a request without a valid one has none to send
+        let url = match (&self.cookie_jar, request.url()) {
+            (Some(_), Ok(url)) => Some(url),
+            _ => None,
+        };
+
+        let with_cookies = url.as_ref().and_then(|url| self.with_cookies(request, url));
+        let request = with_cookies.as_ref().unwrap_or(request);
+
+        let with_resolver = self.with_resolver(request);
+        let request = with_resolver.as_ref().unwrap_or(request);
+
+        let key = Key::new(request);
+
+        let (mut stream, reused) = match self.checkout(&key) {
+            Some(stream) => (stream, true),
+            None => (BufReader::new(request.connect(timeouts)?), false),
+        };
+
+        let (response, reusable) = match request.exchange(&mut stream, true, timeouts) {
+            Ok(exchanged) => exchanged,
+            // the server closed the connection after it was checked, and before it got the request, so the request
+            // goes again (once) over a new connection, as long as its body can be sent again
+            Err(failure) if reused && failure.unanswered && !request.body.is_spent() => {
+                stream = BufReader::new(request.connect(timeouts)?);
+                request.exchange(&mut stream, true, timeouts)?
+            }
+            Err(failure) => return Err(failure.error),
+        };
+
+        if reusable {
+            self.checkin(key, stream);
+        }
+
+        if let (Some(jar), Some(url)) = (&self.cookie_jar, &url) {
+            jar.store(url, &response.headers);
+        }
+
+        Ok(response)
+    }
+}
+
+impl ValidClient for Client {
+    fn new() -> Client {
+        Client {
+            max_idle_per_host: 8,
+            idle_timeout: Duration::from_secs(90),
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            timeout: None,
+            cookie_jar: None,
+            retry: None,
+            resolver: None,
+            pool: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn send(&self, request: &Request) -> Result<Response, RequestError> {
+        let timeouts = request.timeouts(Some(self));
+
+        request.follow_redirects(|request| {
+            let policy = request.retry.as_ref().or(self.retry.as_ref());
+            request.send_retrying(policy, &timeouts, |request| self.send_once(request, &timeouts))
+        })
+    }
+
+    fn max_idle_per_host(&mut self, max_idle_per_host: usize) -> &mut Self {
+        self.max_idle_per_host = max_idle_per_host;
+        self
+    }
+
+    fn idle_timeout(&mut self, idle_timeout: Duration) -> &mut Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    fn connect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    fn read_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    fn write_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn cookie_jar(&mut self, cookie_jar: CookieJar) -> &mut Self {
+        self.cookie_jar = Some(cookie_jar);
+        self
+    }
+
+    fn retry(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    fn resolver(&mut self, resolver: Arc<dyn Resolver>) -> &mut Self {
+        self.resolver = Some(resolver);
+        self
+    }
+}
@@ -0,0 +1,429 @@
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::headers::HeaderMap;
+use crate::url::Url;
+
+/// The `SameSite` attribute of a cookie
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    /// Only sent with requests from the same site
+    Strict,
+    /// Also sent when following a link from another site
+    Lax,
+    /// Sent with every request
+    None,
+}
+
+/// A cookie, as set by a server with `Set-Cookie` (RFC 6265, section 5.2)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    /// The name of the cookie (example: `session`)
+    pub name: String,
+
+    /// The value of the cookie (example: `abc123`)
+    pub value: String,
+
+    /// The domain the cookie is sent to, in lowercase (example: `example.com`)
+    pub domain: String,
+
+    /// Whether the cookie is only sent to `domain` itself, rather than to its subdomains too (set when the server did
+    /// not give a `Domain` attribute)
+    pub host_only: bool,
+
+    /// The path the cookie is sent under (example: `/`)
+    pub path: String,
+
+    /// When the cookie expires, or `None` for a session cookie that lasts as long as the jar
+    pub expires: Option<SystemTime>,
+
+    /// Whether the cookie is only sent over `https`
+    pub secure: bool,
+
+    /// Whether the cookie is hidden from scripts (which makes no difference to this client, but is kept for saving)
+    pub http_only: bool,
+
+    /// The `SameSite` attribute of the cookie, if it was given
+    pub same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header value received in answer to a request for `url`
+    ///
+    /// Returns `None` if the header is malformed, or sets a cookie for a domain that `url` is not part of.
+    pub fn parse(set_cookie: &str, url: &Url) -> Option<Cookie> {
+        let host = url.host()?.to_ascii_lowercase();
+
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url.path()),
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        };
+
+        let mut max_age = None;
+        let mut expires = None;
+
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+
+            match key.to_ascii_lowercase().as_str() {
+                "expires" => expires = parse_date(value).or(expires),
+                "max-age" => {
+                    // only digits, with an optional leading minus, are allowed
+                    let digits = value.strip_prefix('-').unwrap_or(value);
+                    if !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit()) {
+                        max_age = Some(if value.starts_with('-') || digits.bytes().all(|byte| byte == b'0') {
+                            UNIX_EPOCH
+                        } else {
+                            // huge values are capped, rather than overflowing
+                            let seconds = digits.parse::<u32>().unwrap_or(u32::MAX);
+                            SystemTime::now() + Duration::from_secs(u64::from(seconds))
+                        });
+                    }
+                }
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+
+                    if !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    // a cookie for a bare top-level name (example: `com`) would be sent to every site under it
+                    if domain != host && !domain.contains('.') {
+                        return None;
+                    }
+
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => Some(SameSite::Strict),
+                        "lax" => Some(SameSite::Lax),
+                        "none" => Some(SameSite::None),
+                        _ => cookie.same_site,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Max-Age wins over Expires
+        cookie.expires = max_age.or(expires);
+
+        Some(cookie)
+    }
+
+    /// Whether the cookie has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= SystemTime::now())
+    }
+
+    /// Whether the cookie should be sent with a request for `url` (RFC 6265, section 5.4)
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = match url.host() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+
+        let secure_ok = !self.secure || url.scheme() == "https";
+
+        domain_ok && secure_ok && path_matches(url.path(), &self.path) && !self.is_expired()
+    }
+}
+
+/// Whether `host` is `domain`, or a subdomain of it (IP addresses only match themselves)
+fn domain_matches(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+
+    host.parse::<IpAddr>().is_err()
+        && host.len() > domain.len()
+        && host.ends_with(domain)
+        && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+}
+
+/// Whether a request path is under a cookie path (RFC 6265, section 5.1.4)
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    let request_path = if request_path.is_empty() { "/" } else { request_path };
+
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+/// The path a cookie is sent under when it does not give one: the request path up to its last `/`
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(last) => request_path[..last].to_string(),
+    }
+}
+
+/// Parse a cookie date (RFC 6265, section 5.1.1), which is lenient about the format (example:
+/// `Wed, 21 Oct 2015 07:28:00 GMT`)
+pub(crate) fn parse_date(date: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+
+    let is_delimiter = |c: char| !(c.is_ascii_alphanumeric() || c == ':');
+
+    for token in date.split(is_delimiter).filter(|token| !token.is_empty()) {
+        let digits = token.bytes().take_while(u8::is_ascii_digit).count();
+
+        if time.is_none() && token.contains(':') {
+            let mut fields = token.splitn(3, ':').map(|field| {
+                let digits: String = field.chars().take_while(char::is_ascii_digit).collect();
+                if (1..=2).contains(&digits.len()) {
+                    digits.parse::<u32>().ok()
+                } else {
+                    None
+                }
+            });
+            if let (Some(Some(hour)), Some(Some(minute)), Some(Some(second))) = (fields.next(), fields.next(), fields.next()) {
+                time = Some((hour, minute, second));
+                continue;
+            }
+        }
+        if day.is_none() && (1..=2).contains(&digits) {
+            day = token[..digits].parse::<u32>().ok();
+            continue;
+        }
+        if month.is_none() && token.len() >= 3 {
+            let prefix = token[..3].to_ascii_lowercase();
+            if let Some(index) = MONTHS.iter().position(|&name| name == prefix) {
+                month = Some(index as u32 + 1);
+                continue;
+            }
+        }
+        if year.is_none() && (2..=4).contains(&digits) {
+            year = token[..digits].parse::<i64>().ok();
+            continue;
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day, month, mut year) = (day?, month?, year?);
+
+    // two-digit years are 1970 to 2069
+    if (70..=99).contains(&year) {
+        year += 1900;
+    } else if (0..=69).contains(&year) {
+        year += 2000;
+    }
+
+    if !(1..=31).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let seconds = days_from_civil(year, month, day) * 86400 + i64::from(hour * 3600 + minute * 60 + second);
+
+    if seconds >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+    } else {
+        Some(UNIX_EPOCH)
+    }
+}
+
+/// The number of days from 1970-01-01 to a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// A cookie jar, which stores the cookies servers set and sends them back with later requests
+///
+/// Clones of a jar share the same cookies, so a jar given to a `Client` can still be read (or saved) afterwards.
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::client::{Client, ValidClient};
+/// use quickhttp::CookieJar;
+///
+/// let jar = CookieJar::new();
+/// let mut client = Client::new();
+/// client.cookie_jar(jar.clone());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<Cookie>>>,
+}
+
+impl CookieJar {
+    /// Create an empty cookie jar
+    pub fn new() -> CookieJar {
+        CookieJar::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Cookie>> {
+        self.cookies.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Store a cookie, replacing any with the same name, domain and path (an expired cookie just removes them)
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.lock();
+
+        cookies.retain(|existing| {
+            !(existing.name == cookie.name && existing.domain == cookie.domain && existing.path == cookie.path)
+        });
+
+        if !cookie.is_expired() {
+            cookies.push(cookie);
+        }
+    }
+
+    /// Store the cookies from the `Set-Cookie` headers of a response to a request for `url`
+    pub fn store(&self, url: &Url, headers: &HeaderMap) {
+        for set_cookie in headers.get_all("Set-Cookie") {
+            if let Some(cookie) = Cookie::parse(set_cookie, url) {
+                self.insert(cookie);
+            }
+        }
+    }
+
+    /// The value of the `Cookie` header to send with a request for `url`, if any cookies match it
+    ///
+    /// Cookies with longer paths come first (RFC 6265, section 5.4).
+    pub fn cookie_header(&self, url: &Url) -> Option<String> {
+        let mut cookies = self.lock();
+        cookies.retain(|cookie| !cookie.is_expired());
+
+        let mut matching: Vec<&Cookie> = cookies.iter().filter(|cookie| cookie.matches(url)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+
+        let pairs: Vec<String> = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Every cookie in the jar that has not expired
+    pub fn cookies(&self) -> Vec<Cookie> {
+        self.lock().iter().filter(|cookie| !cookie.is_expired()).cloned().collect()
+    }
+
+    /// Remove every cookie from the jar
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Save the cookies to a file, in the Netscape `cookies.txt` format used by curl and browsers
+    ///
+    /// Session cookies are saved with an expiry of `0`. The `SameSite` attribute is not part of the format, so it is
+    /// not saved.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = String::from("# Netscape HTTP Cookie File\n");
+
+        for cookie in self.cookies() {
+            let expires = cookie
+                .expires
+                .and_then(|expires| expires.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |expires| expires.as_secs());
+
+            let bool_field = |value: bool| if value { "TRUE" } else { "FALSE" };
+            let domain = if cookie.host_only {
+                cookie.domain.clone()
+            } else {
+                format!(".{}", cookie.domain)
+            };
+
+            file.push_str(&format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.http_only { "#HttpOnly_" } else { "" },
+                domain,
+                bool_field(!cookie.host_only),
+                cookie.path,
+                bool_field(cookie.secure),
+                expires,
+                cookie.name,
+                cookie.value
+            ));
+        }
+
+        std::fs::write(path, file)
+    }
+
+    /// Load a cookie jar saved by [`CookieJar::save`] (or by curl), skipping any cookies that have expired
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<CookieJar> {
+        let jar = CookieJar::new();
+
+        for line in std::fs::read_to_string(path)?.lines() {
+            let (http_only, line) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [domain, include_subdomains, path, secure, expires, name, value] = fields[..] else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid cookie line: {}", line)));
+            };
+
+            let expires = match expires.parse::<u64>() {
+                Ok(0) => None,
+                Ok(expires) => Some(UNIX_EPOCH + Duration::from_secs(expires)),
+                Err(_) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid cookie expiry: {}", expires)))
+                }
+            };
+
+            jar.insert(Cookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+                host_only: include_subdomains != "TRUE",
+                path: path.to_string(),
+                expires,
+                secure: secure == "TRUE",
+                http_only,
+                same_site: None,
+            });
+        }
+
+        Ok(jar)
+    }
+}
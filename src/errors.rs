@@ -1,58 +1,285 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// The kind of an error, so that callers can react to different failures (for example, retrying a timeout but not an invalid URL)
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::errors::{Error, ErrorKind, RequestError, TimeoutKind};
+///
+/// let error = RequestError::with_kind(ErrorKind::Timeout(TimeoutKind::Read), "read timed out".to_string());
+/// match error.kind() {
+///     ErrorKind::Timeout(_) | ErrorKind::Connect => println!("worth retrying"),
+///     _ => println!("giving up"),
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The host name could not be resolved
+    Dns,
+    /// The connection to the server could not be established
+    Connect,
+    /// The TLS handshake failed, or TLS is not available
+    Tls,
+    /// An operation took too long
+    Timeout(TimeoutKind),
+    /// Reading from or writing to the connection failed
+    Io,
+    /// The server sent a response that could not be understood
+    InvalidResponse,
+    /// A redirect chain was longer than allowed
+    TooManyRedirects,
+    /// A URL could not be parsed, or uses an unsupported scheme
+    InvalidUrl,
+    /// The request is missing something, or is otherwise invalid
+    InvalidRequest,
+    /// A value could not be parsed as JSON, or converted to or from it
+    InvalidJson,
+    /// Any other error
+    Other,
+}
+
+impl core::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let description = match self {
+            ErrorKind::Dns => "DNS resolution failed",
+            ErrorKind::Connect => "connection failed",
+            ErrorKind::Tls => "TLS error",
+            ErrorKind::Timeout(TimeoutKind::Connect) => "connect timed out",
+            ErrorKind::Timeout(TimeoutKind::Read) => "read timed out",
+            ErrorKind::Timeout(TimeoutKind::Write) => "write timed out",
+            ErrorKind::Timeout(TimeoutKind::Deadline) => "request deadline exceeded",
+            ErrorKind::Io => "I/O error",
+            ErrorKind::InvalidResponse => "invalid response",
+            ErrorKind::TooManyRedirects => "too many redirects",
+            ErrorKind::InvalidUrl => "invalid URL",
+            ErrorKind::InvalidRequest => "invalid request",
+            ErrorKind::InvalidJson => "invalid JSON",
+            ErrorKind::Other => "error",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// Which timeout expired, for errors of kind [`ErrorKind::Timeout`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimeoutKind {
+    /// The connection (including any TLS handshake) was not established in time
+    Connect,
+    /// The server sent nothing for longer than the read timeout
+    Read,
+    /// The request could not be written for longer than the write timeout
+    Write,
+    /// The whole request took longer than its overall deadline
+    Deadline,
+}
+
+/// The underlying cause of an error, such as an `io::Error`
+type Source = Arc<dyn std::error::Error + Send + Sync>;
+
 /// Trait defining the error type for the library
 pub trait Error {
+    /// Create an error with a message, and the default kind for the error type
     fn new(message: String) -> Self;
+
+    /// Create an error of the given kind with a message
+    fn with_kind(kind: ErrorKind, message: String) -> Self;
+
+    /// Attach the underlying cause of the error, returned by `source()`
+    fn with_source<E: std::error::Error + Send + Sync + 'static>(self, source: E) -> Self;
+
+    /// The kind of the error
+    fn kind(&self) -> ErrorKind;
+
+    /// The message describing the error
+    fn message(&self) -> &str;
+}
+
+/// Implement the error trait, `Display` and `std::error::Error` for an error type
+macro_rules! error_type {
+    ($name:ident, $default_kind:expr) => {
+        impl Error for $name {
+            fn new(message: String) -> $name {
+                $name::with_kind($default_kind, message)
+            }
+
+            fn with_kind(kind: ErrorKind, message: String) -> $name {
+                $name {
+                    message,
+                    kind,
+                    source: None,
+                }
+            }
+
+            fn with_source<E: std::error::Error + Send + Sync + 'static>(mut self, source: E) -> $name {
+                self.source = Some(Arc::new(source));
+                self
+            }
+
+            fn kind(&self) -> ErrorKind {
+                self.kind
+            }
+
+            fn message(&self) -> &str {
+                &self.message
+            }
+        }
+
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                write!(f, "{}: {}", stringify!($name), self.message)
+            }
+        }
+
+        impl std::error::Error for $name {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                self.source
+                    .as_deref()
+                    .map(|source| source as &(dyn std::error::Error + 'static))
+            }
+        }
+    };
 }
 
 /// Error type for a builder (for example, if a required field is missing)
 #[derive(Clone, Debug)]
 pub struct BuilderError {
     pub(crate) message: String,
+    pub(crate) kind: ErrorKind,
+    source: Option<Source>,
 }
 
-impl Error for BuilderError {
-    fn new(message: String) -> BuilderError {
-        BuilderError { message }
-    }
-}
+error_type!(BuilderError, ErrorKind::InvalidRequest);
 
 /// Error type for a request (for example, if the request fails)
 #[derive(Clone, Debug)]
 pub struct RequestError {
     pub(crate) message: String,
+    pub(crate) kind: ErrorKind,
+    source: Option<Source>,
 }
 
-impl Error for RequestError {
-    fn new(message: String) -> RequestError {
-        RequestError { message }
-    }
-}
+error_type!(RequestError, ErrorKind::Other);
 
 /// Error type for a response (for example, if the response is invalid)
 #[derive(Clone, Debug)]
 pub struct ResponseError {
     pub(crate) message: String,
+    pub(crate) kind: ErrorKind,
+    source: Option<Source>,
+}
+
+error_type!(ResponseError, ErrorKind::InvalidResponse);
+
+/// Error type for JSON (for example, if a body is not valid JSON)
+#[derive(Clone, Debug)]
+pub struct JsonError {
+    pub(crate) message: String,
+    pub(crate) kind: ErrorKind,
+    source: Option<Source>,
 }
 
-impl Error for ResponseError {
-    fn new(message: String) -> ResponseError {
-        ResponseError { message }
+error_type!(JsonError, ErrorKind::InvalidJson);
+
+/// A builder error means the request could not be sent, so it converts to a request error of the same kind
+impl From<BuilderError> for RequestError {
+    fn from(error: BuilderError) -> RequestError {
+        RequestError::with_kind(error.kind, error.message.clone()).with_source(error)
     }
 }
 
-impl core::fmt::Display for BuilderError {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "BuilderError: {}", self.message)
+/// An invalid response means the request failed, so it converts to a request error of the same kind
+impl From<ResponseError> for RequestError {
+    fn from(error: ResponseError) -> RequestError {
+        RequestError::with_kind(error.kind, error.message.clone()).with_source(error)
     }
 }
 
-impl core::fmt::Display for RequestError {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "RequestError: {}", self.message)
+/// A response body that is not valid JSON is an invalid response, so it converts to a response error of the same kind
+impl From<JsonError> for ResponseError {
+    fn from(error: JsonError) -> ResponseError {
+        ResponseError::with_kind(error.kind, error.message.clone()).with_source(error)
     }
 }
 
-impl core::fmt::Display for ResponseError {
+impl RequestError {
+    /// Create an error of the given kind from an I/O error, which is kept as the source
+    ///
+    /// I/O errors that report a timeout are given the `Timeout` kind instead, with `timeout` saying which one expired.
+    pub(crate) fn io(kind: ErrorKind, timeout: TimeoutKind, message: &str, error: std::io::Error) -> RequestError {
+        let kind = match error.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => ErrorKind::Timeout(timeout),
+            _ => kind,
+        };
+
+        RequestError::with_kind(kind, format!("{}: {}", message, error)).with_source(error)
+    }
+
+    /// Create an error for a host that could not be connected to at any of its addresses, keeping the failed attempts
+    /// as the source
+    ///
+    /// The error has the `Timeout` kind if every attempt timed out, and the `Connect` kind otherwise.
+    pub(crate) fn attempts(attempts: Vec<(SocketAddr, std::io::Error)>) -> RequestError {
+        let timed_out = |error: &std::io::Error| {
+            matches!(error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)
+        };
+        let kind = if attempts.iter().all(|(_, error)| timed_out(error)) {
+            ErrorKind::Timeout(TimeoutKind::Connect)
+        } else {
+            ErrorKind::Connect
+        };
+
+        let attempts = ConnectAttempts { attempts };
+        RequestError::with_kind(kind, format!("could not connect to server: {}", attempts)).with_source(attempts)
+    }
+}
+
+/// The connection attempts made to each of the addresses a host resolved to, when none of them succeeded
+///
+/// This is the source of the `RequestError`, so the attempts can be looked at one by one.
+///
+/// # Examples
+/// ```rust
+/// use std::error::Error as _;
+///
+/// use quickhttp::builder::{Builder, ValidBuilder};
+/// use quickhttp::errors::ConnectAttempts;
+/// use quickhttp::request::ValidRequest;
+///
+/// let error = Builder::new()
+///     .uri("http://localhost:9/".to_string())
+///     .build()
+///     .unwrap()
+///     .send()
+///     .unwrap_err();
+///
+/// if let Some(attempts) = error.source().and_then(|source| source.downcast_ref::<ConnectAttempts>()) {
+///     for (addr, error) in &attempts.attempts {
+///         println!("{}: {}", addr, error);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ConnectAttempts {
+    /// The address of each attempt, with the error it failed with, in the order the attempts were started
+    pub attempts: Vec<(SocketAddr, std::io::Error)>,
+}
+
+impl core::fmt::Display for ConnectAttempts {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "ResponseError: {}", self.message)
+        for (i, (addr, error)) in self.attempts.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {}", addr, error)?;
+        }
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl std::error::Error for ConnectAttempts {
+    /// The error from the last attempt, so that walking the sources of an error still ends at an `io::Error`
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.attempts.last().map(|(_, error)| error as &(dyn std::error::Error + 'static))
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+/// A map of header fields, which matches names case-insensitively, keeps the order fields were added in, and can hold
+/// more than one value for a name (as with `Set-Cookie`)
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::HeaderMap;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.append("Set-Cookie".to_string(), "a=1".to_string());
+/// headers.append("set-cookie".to_string(), "b=2".to_string());
+///
+/// assert_eq!(headers.get("SET-COOKIE"), Some("a=1"));
+/// assert_eq!(headers.get_all("Set-Cookie").collect::<Vec<_>>(), ["a=1", "b=2"]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    /// Create an empty header map
+    pub fn new() -> HeaderMap {
+        HeaderMap { entries: Vec::new() }
+    }
+
+    /// The first value of a header (example: `text/html` for `Content-Type`)
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value of a header, in the order they were added
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value of a header joined into one comma-separated list, which is how repeated fields are read
+    /// (RFC 9110, section 5.3)
+    pub(crate) fn joined(&self, name: &str) -> Option<String> {
+        let values: Vec<&str> = self.get_all(name).collect();
+
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.join(", "))
+        }
+    }
+
+    /// Whether the header has at least one value
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Add a value for a header, keeping any values it already has
+    pub fn append(&mut self, name: String, value: String) {
+        self.entries.push((name, value));
+    }
+
+    /// Set the value of a header, replacing any values it already has, and return the first of the old values
+    ///
+    /// The header keeps its place if it was already set.
+    pub fn insert(&mut self, name: String, value: String) -> Option<String> {
+        let position = match self.entries.iter().position(|(key, _)| key.eq_ignore_ascii_case(&name)) {
+            Some(position) => position,
+            None => {
+                self.entries.push((name, value));
+                return None;
+            }
+        };
+
+        let (_, old) = std::mem::replace(&mut self.entries[position], (name, value));
+
+        // drop any later values for the same name
+        let name = self.entries[position].0.clone();
+        let mut index = 0;
+        self.entries.retain(|(key, _)| {
+            index += 1;
+            index - 1 <= position || !key.eq_ignore_ascii_case(&name)
+        });
+
+        Some(old)
+    }
+
+    /// Remove every value of a header, returning the first of them
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let old = self.get(name).map(str::to_string);
+        self.entries.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        old
+    }
+
+    /// Keep only the fields for which `keep` returns true
+    pub fn retain(&mut self, mut keep: impl FnMut(&str, &str) -> bool) {
+        self.entries.retain(|(key, value)| keep(key, value));
+    }
+
+    /// The header fields, as name and value pairs, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// The names of the header fields, once for each value
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(key, _)| key.as_str())
+    }
+
+    /// The number of header fields, counting each value of a header separately
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no header fields
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Get the first value of a header, panicking if it is not set (like indexing a `HashMap`)
+impl core::ops::Index<&str> for HeaderMap {
+    type Output = String;
+
+    fn index(&self, name: &str) -> &String {
+        match self.entries.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)) {
+            Some((_, value)) => value,
+            None => panic!("header not set: {}", name),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a str, &'a str);
+    type IntoIter = std::iter::Map<std::slice::Iter<'a, (String, String)>, fn(&'a (String, String)) -> (&'a str, &'a str)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+impl FromIterator<(String, String)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> HeaderMap {
+        HeaderMap {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<(String, String)> for HeaderMap {
+    fn extend<I: IntoIterator<Item = (String, String)>>(&mut self, iter: I) {
+        self.entries.extend(iter);
+    }
+}
+
+impl From<HashMap<String, String>> for HeaderMap {
+    fn from(headers: HashMap<String, String>) -> HeaderMap {
+        headers.into_iter().collect()
+    }
+}
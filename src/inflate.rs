@@ -0,0 +1,429 @@
+/// The most a response body may decompress to, so that a small compressed body cannot use up all the memory there is
+pub(crate) const MAX_OUTPUT: usize = 128 * 1024 * 1024;
+
+/// The longest Huffman code allowed by deflate
+const MAX_BITS: usize = 15;
+
+/// The base lengths for length symbols 257 to 285, and how many extra bits follow each
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+
+/// The base distances for distance symbols 0 to 29, and how many extra bits follow each
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// The order the code length code lengths are sent in, for dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Reads the bits of a deflate stream, least significant bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, needed: u32) -> Result<u32, String> {
+        while self.count < needed {
+            let byte = match self.data.get(self.pos) {
+                Some(&byte) => byte,
+                None => return Err("compressed data ended early".to_string()),
+            };
+            self.buffer |= u32::from(byte) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1u64 << needed) - 1) as u32;
+        self.buffer >>= needed;
+        self.count -= needed;
+        Ok(value)
+    }
+
+    /// Skip to the start of the next byte, as stored blocks are byte-aligned
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols in code order
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Build the code from the code length of each symbol (`0` for symbols that are not used)
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // a code with more codes of some length than there is room for cannot be decoded (RFC 1951, section 3.2.2)
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err("invalid Huffman code lengths".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+/// Fail if adding `more` bytes to the output would take it over `limit`
+fn reserve(out: &[u8], more: usize, limit: usize) -> Result<(), String> {
+    if out.len() + more > limit {
+        return Err(format!("decompressed data is larger than the limit of {} bytes", limit));
+    }
+    Ok(())
+}
+
+/// Decompress raw deflate data (RFC 1951) to at most `limit` bytes, returning the data and how many input bytes it
+/// took up
+pub(crate) fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), String> {
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => stored(&mut reader, &mut out, limit)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                codes(&mut reader, &mut out, &literals, &distances, limit)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                codes(&mut reader, &mut out, &literals, &distances, limit)?;
+            }
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+
+        if last {
+            return Ok((out, reader.pos));
+        }
+    }
+}
+
+/// Copy a stored (uncompressed) block
+fn stored(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    reader.align();
+
+    let header = match reader.data.get(reader.pos..reader.pos + 4) {
+        Some(header) => header,
+        None => return Err("compressed data ended early".to_string()),
+    };
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if length != !complement {
+        return Err("invalid stored block length".to_string());
+    }
+    reader.pos += 4;
+    reserve(out, length as usize, limit)?;
+
+    match reader.data.get(reader.pos..reader.pos + length as usize) {
+        Some(block) => out.extend_from_slice(block),
+        None => return Err("compressed data ended early".to_string()),
+    }
+    reader.pos += length as usize;
+
+    Ok(())
+}
+
+/// The fixed codes used by blocks of type 1 (RFC 1951, section 3.2.6)
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// Read the codes sent at the start of a block of type 2 (RFC 1951, section 3.2.7)
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    if literal_count > 286 || distance_count > 30 {
+        return Err("too many codes in dynamic block".to_string());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match index {
+                0 => return Err("repeated code length with no previous length".to_string()),
+                _ => (lengths[index - 1], 3 + reader.bits(2)? as usize),
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+
+        if index + repeat > lengths.len() {
+            return Err("too many code lengths in dynamic block".to_string());
+        }
+        lengths[index..index + repeat].fill(value);
+        index += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err("dynamic block has no end-of-block code".to_string());
+    }
+
+    let literals = Huffman::new(&lengths[..literal_count])?;
+    let distances = Huffman::new(&lengths[literal_count..])?;
+    Ok((literals, distances))
+}
+
+/// Decode the symbols of a compressed block, up to its end-of-block code
+fn codes(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+    limit: usize,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+
+        match symbol {
+            0..=255 => {
+                reserve(out, 1, limit)?;
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length = LENGTH_BASE[index] as usize + reader.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err("invalid distance code".to_string());
+                }
+                let distance =
+                    DISTANCE_BASE[index] as usize + reader.bits(u32::from(DISTANCE_EXTRA[index]))? as usize;
+
+                if distance > out.len() {
+                    return Err("distance is too far back".to_string());
+                }
+                reserve(out, length, limit)?;
+
+                // the copy may overlap the bytes it produces, so it goes one byte at a time
+                let start = out.len() - distance;
+                for offset in 0..length {
+                    out.push(out[start + offset]);
+                }
+            }
+            _ => return Err("invalid literal/length code".to_string()),
+        }
+    }
+}
+
+/// Decompress gzip data (RFC 1952) to at most `limit` bytes, including any further members concatenated after the first
+pub(crate) fn gunzip(mut data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+
+    loop {
+        let (member, used) = gunzip_member(data, limit - out.len())?;
+        out.extend_from_slice(&member);
+        data = &data[used..];
+
+        if !data.starts_with(&[0x1f, 0x8b]) {
+            return Ok(out);
+        }
+    }
+}
+
+/// Decompress one gzip member, returning the data and how many input bytes it took up
+fn gunzip_member(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), String> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let truncated = || "gzip data ended early".to_string();
+
+    if data.len() < 10 || data[0] != 0x1f || data[1] != 0x8b {
+        return Err("not gzip data".to_string());
+    }
+    if data[2] != 8 {
+        return Err(format!("unsupported gzip compression method: {}", data[2]));
+    }
+
+    let flags = data[3];
+    let mut pos = 10;
+
+    if flags & FEXTRA != 0 {
+        let length = data.get(pos..pos + 2).ok_or_else(truncated)?;
+        pos += 2 + u16::from_le_bytes([length[0], length[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data.get(pos..).and_then(|rest| rest.iter().position(|&byte| byte == 0));
+            pos += end.ok_or_else(truncated)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+
+    let (out, used) = inflate(data.get(pos..).ok_or_else(truncated)?, limit)?;
+    pos += used;
+
+    let trailer = data.get(pos..pos + 8).ok_or_else(truncated)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+
+    if crc != crc32(&out) {
+        return Err("gzip checksum does not match".to_string());
+    }
+    if size != out.len() as u32 {
+        return Err("gzip length does not match".to_string());
+    }
+
+    Ok((out, pos + 8))
+}
+
+/// Whether data starts with a valid zlib header
+fn is_zlib(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] & 0x0f == 8 && (u16::from(data[0]) << 8 | u16::from(data[1])) % 31 == 0
+}
+
+/// Decompress zlib data (RFC 1950) to at most `limit` bytes
+pub(crate) fn unzlib(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if !is_zlib(data) {
+        return Err("not zlib data".to_string());
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported".to_string());
+    }
+
+    let (out, used) = inflate(&data[2..], limit)?;
+
+    let trailer = match data.get(2 + used..2 + used + 4) {
+        Some(trailer) => trailer,
+        None => return Err("zlib data ended early".to_string()),
+    };
+    if u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != adler32(&out) {
+        return Err("zlib checksum does not match".to_string());
+    }
+
+    Ok(out)
+}
+
+/// Decode a body sent with the given `Content-Encoding` to at most `limit` bytes, returning `None` for encodings that
+/// are not supported
+///
+/// `deflate` is meant to be zlib data, but some servers send raw deflate data, so that is accepted too.
+pub(crate) fn decode(encoding: &str, body: &[u8], limit: usize) -> Option<Result<Vec<u8>, String>> {
+    let encoding = encoding.trim().to_ascii_lowercase();
+
+    match encoding.as_str() {
+        "identity" => Some(Ok(body.to_vec())),
+        "gzip" | "x-gzip" => Some(gunzip(body, limit)),
+        "deflate" => Some(unzlib(body, limit).or_else(|error| match inflate(body, limit) {
+            Ok((out, _)) => Ok(out),
+            // the error that explains the failure is the one for whichever the data looked like
+            Err(raw) if !is_zlib(body) => Err(raw),
+            Err(_) => Err(error),
+        })),
+        _ => None,
+    }
+}
+
+/// The CRC-32 checksum used by gzip
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut n = 0;
+        while n < 256 {
+            let mut c = n as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+                k += 1;
+            }
+            table[n] = c;
+            n += 1;
+        }
+        table
+    };
+
+    !data
+        .iter()
+        .fold(!0u32, |crc, &byte| TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8))
+}
+
+/// The Adler-32 checksum used by zlib
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + u32::from(byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+
+    (b << 16) | a
+}
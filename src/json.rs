@@ -0,0 +1,491 @@
+use crate::errors::{Error, JsonError};
+
+/// How deeply arrays and objects may be nested before a document is rejected
+const MAX_DEPTH: usize = 128;
+
+/// A JSON value (RFC 8259)
+///
+/// Objects keep their members in the order they were added or parsed. Numbers are held as `f64`, so integers beyond
+/// 2^53 lose precision.
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::Json;
+///
+/// let value = Json::parse(r#"{"name": "John", "tags": ["a", "b"], "age": 42}"#).unwrap();
+///
+/// assert_eq!(value["name"].as_str(), Some("John"));
+/// assert_eq!(value["tags"][1].as_str(), Some("b"));
+/// assert_eq!(value["age"].as_i64(), Some(42));
+/// assert!(value["missing"].is_null());
+/// assert_eq!(value.to_string(), r#"{"name":"John","tags":["a","b"],"age":42}"#);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Bool(bool),
+    /// A number (example: `4.5`)
+    Number(f64),
+    /// A string (example: `"John"`)
+    String(String),
+    /// An array (example: `[1, 2]`)
+    Array(Vec<Json>),
+    /// An object, as its members in order (example: `{"name": "John"}`)
+    Object(Vec<(String, Json)>),
+}
+
+/// Returned when indexing finds nothing, like a missing member would be in JavaScript
+static NULL: Json = Json::Null;
+
+impl Json {
+    /// Parse a JSON document, which must be a single value with nothing but whitespace around it
+    pub fn parse(input: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            pos: 0,
+        };
+
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos < parser.input.len() {
+            return Err(parser.fail("unexpected data after the value"));
+        }
+
+        Ok(value)
+    }
+
+    /// The member of an object with the given name (the first one, if there are several)
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(key, _)| key == name).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is `null`
+    pub fn is_null(&self) -> bool {
+        *self == Json::Null
+    }
+
+    /// The value of a boolean
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of a number
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of a number, if it is a whole number that fits in an `i64`
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(value) if value.fract() == 0.0 && *value >= i64::MIN as f64 && *value < i64::MAX as f64 => {
+                Some(*value as i64)
+            }
+            _ => None,
+        }
+    }
+
+    /// The value of a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The elements of an array
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// The members of an object
+    pub fn as_object(&self) -> Option<&[(String, Json)]> {
+        match self {
+            Json::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+}
+
+/// Get a member of an object, or `Json::Null` if there is no such member (or the value is not an object)
+impl core::ops::Index<&str> for Json {
+    type Output = Json;
+
+    fn index(&self, name: &str) -> &Json {
+        self.get(name).unwrap_or(&NULL)
+    }
+}
+
+/// Get an element of an array, or `Json::Null` if there is no such element (or the value is not an array)
+impl core::ops::Index<usize> for Json {
+    type Output = Json;
+
+    fn index(&self, index: usize) -> &Json {
+        self.as_array().and_then(|values| values.get(index)).unwrap_or(&NULL)
+    }
+}
+
+/// Serialize the value as compact JSON (a number that is not finite is written as `null`)
+impl core::fmt::Display for Json {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) if value.is_finite() => write!(f, "{}", value),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Write a string in quotes, escaping the characters that must be escaped
+fn write_string(f: &mut core::fmt::Formatter, value: &str) -> core::fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            '\u{08}' => write!(f, "\\b")?,
+            '\u{0C}' => write!(f, "\\f")?,
+            c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl core::str::FromStr for Json {
+    type Err = JsonError;
+
+    fn from_str(input: &str) -> Result<Json, JsonError> {
+        Json::parse(input)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Json {
+        Json::Number(value)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Json {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(value: i32) -> Json {
+        Json::Number(f64::from(value))
+    }
+}
+
+impl From<u32> for Json {
+    fn from(value: u32) -> Json {
+        Json::Number(f64::from(value))
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Json {
+        Json::Array(values)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+/// A strict JSON parser: no comments, trailing commas, single quotes or other extensions are accepted
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn fail(&self, message: &str) -> JsonError {
+        JsonError::new(format!("{} at byte {}", message, self.pos))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &str, value: Json) -> Result<Json, JsonError> {
+        if self.input[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(value)
+        } else {
+            Err(self.fail("invalid literal"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, JsonError> {
+        if depth > MAX_DEPTH {
+            return Err(self.fail("nested too deeply"));
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(self.fail("unexpected end of input")),
+            Some(b'n') => self.expect_literal("null", Json::Null),
+            Some(b't') => self.expect_literal("true", Json::Bool(true)),
+            Some(b'f') => self.expect_literal("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => self.array(depth),
+            Some(b'{') => self.object(depth),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.fail("unexpected character")),
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.pos += 1;
+        let mut values = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.value(depth + 1)?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.fail("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, JsonError> {
+        self.pos += 1;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.fail("expected a member name"));
+            }
+            let key = self.string()?;
+
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.fail("expected ':'"));
+            }
+            self.pos += 1;
+
+            members.push((key, self.value(depth + 1)?));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.fail("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        let digits = |parser: &mut Parser| {
+            let start = parser.pos;
+            while parser.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+                parser.pos += 1;
+            }
+            parser.pos - start
+        };
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+
+        // no leading zeros, except for a lone zero before a fraction or exponent
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                digits(self);
+            }
+            _ => return Err(self.fail("invalid number")),
+        }
+
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if digits(self) == 0 {
+                return Err(self.fail("invalid number"));
+            }
+        }
+
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if digits(self) == 0 {
+                return Err(self.fail("invalid number"));
+            }
+        }
+
+        // the number is plain ASCII, so this cannot fail
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap_or_default();
+        match text.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(Json::Number(value)),
+            _ => Err(JsonError::new(format!("number out of range at byte {}", start))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.pos += 1;
+        let mut value = Vec::new();
+
+        loop {
+            match self.peek() {
+                None => return Err(self.fail("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    // the input is a `str`, and escapes are decoded to whole characters, so this is valid UTF-8
+                    return String::from_utf8(value).map_err(|_| self.fail("invalid UTF-8 in string"));
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{08}',
+                        Some(b'f') => '\u{0C}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode_escape()?,
+                        _ => return Err(self.fail("invalid escape")),
+                    };
+                    self.pos += 1;
+                    let mut buffer = [0; 4];
+                    value.extend_from_slice(escaped.encode_utf8(&mut buffer).as_bytes());
+                }
+                Some(byte) if byte < 0x20 => return Err(self.fail("control character in string")),
+                Some(byte) => {
+                    value.push(byte);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    /// Decode a `\uXXXX` escape (with `pos` on the `u`, and left on its last digit), combining a surrogate pair into
+    /// one character
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.hex4(self.pos + 1)?;
+        self.pos += 4;
+
+        let code = match high {
+            0xD800..=0xDBFF => {
+                if self.input.get(self.pos + 1..self.pos + 3) != Some(b"\\u") {
+                    return Err(self.fail("unpaired surrogate"));
+                }
+                let low = self.hex4(self.pos + 3)?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.fail("unpaired surrogate"));
+                }
+                self.pos += 6;
+                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return Err(self.fail("unpaired surrogate")),
+            code => code,
+        };
+
+        char::from_u32(code).ok_or_else(|| self.fail("invalid escape"))
+    }
+
+    fn hex4(&self, start: usize) -> Result<u32, JsonError> {
+        let digits = self
+            .input
+            .get(start..start + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()));
+
+        match digits {
+            Some(digits) => u32::from_str_radix(digits, 16).map_err(|_| self.fail("invalid escape")),
+            None => Err(self.fail("invalid escape")),
+        }
+    }
+}
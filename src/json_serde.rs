@@ -0,0 +1,572 @@
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+
+use crate::errors::{Error, JsonError};
+use crate::json::Json;
+
+impl Json {
+    /// Convert a value to JSON, the way it would be serialized (example: a struct deriving `Serialize`)
+    ///
+    /// Only available with the `serde` feature. Map keys must be strings, numbers or booleans.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use quickhttp::Json;
+    ///
+    /// let value = Json::from_serde(&vec![("name", "John")]).unwrap();
+    ///
+    /// assert_eq!(value.to_string(), r#"[["name","John"]]"#);
+    /// ```
+    pub fn from_serde<T: Serialize + ?Sized>(value: &T) -> Result<Json, JsonError> {
+        value.serialize(Serializer)
+    }
+
+    /// Convert JSON to a value, the way it would be deserialized (example: a struct deriving `Deserialize`)
+    ///
+    /// Only available with the `serde` feature.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use quickhttp::Json;
+    ///
+    /// let value = Json::parse("[1, 2, 3]").unwrap();
+    ///
+    /// assert_eq!(value.into_serde::<Vec<u8>>().unwrap(), vec![1, 2, 3]);
+    /// ```
+    pub fn into_serde<T: DeserializeOwned>(self) -> Result<T, JsonError> {
+        T::deserialize(self)
+    }
+}
+
+impl ser::Error for JsonError {
+    fn custom<T: core::fmt::Display>(message: T) -> JsonError {
+        JsonError::new(message.to_string())
+    }
+}
+
+impl de::Error for JsonError {
+    fn custom<T: core::fmt::Display>(message: T) -> JsonError {
+        JsonError::new(message.to_string())
+    }
+}
+
+impl Serialize for Json {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+
+        match self {
+            Json::Null => serializer.serialize_unit(),
+            Json::Bool(value) => serializer.serialize_bool(*value),
+            Json::Number(value) => serializer.serialize_f64(*value),
+            Json::String(value) => serializer.serialize_str(value),
+            Json::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Json::Object(members) => {
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+                for (key, value) in members {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Json {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Json, D::Error> {
+        deserializer.deserialize_any(JsonVisitor)
+    }
+}
+
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = Json;
+
+    fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "any JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Json, D::Error> {
+        de::Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Json, E> {
+        Ok(Json::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Json, E> {
+        Ok(Json::Number(value as f64))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Json, E> {
+        Ok(Json::Number(value as f64))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Json, E> {
+        Ok(Json::Number(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Json, E> {
+        Ok(Json::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Json, E> {
+        Ok(Json::String(value))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Json::Array(values))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Json, A::Error> {
+        let mut members = Vec::new();
+        while let Some(member) = map.next_entry()? {
+            members.push(member);
+        }
+        Ok(Json::Object(members))
+    }
+}
+
+/// Reads a typed value out of JSON, as `Json::into_serde`
+impl<'de> de::Deserializer<'de> for Json {
+    type Error = JsonError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, JsonError> {
+        match self {
+            Json::Null => visitor.visit_unit(),
+            Json::Bool(value) => visitor.visit_bool(value),
+            Json::Number(value) => match Json::Number(value).as_i64() {
+                Some(whole) if whole >= 0 => visitor.visit_u64(whole as u64),
+                Some(whole) => visitor.visit_i64(whole),
+                None => visitor.visit_f64(value),
+            },
+            Json::String(value) => visitor.visit_string(value),
+            Json::Array(values) => {
+                let mut seq = SeqDeserializer::new(values.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Json::Object(members) => {
+                let mut map = MapDeserializer::new(keyed(members));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, JsonError> {
+        match self {
+            Json::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, JsonError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // a unit variant is a string, and any other variant an object with the variant as its only member
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, JsonError> {
+        match self {
+            Json::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Json::Object(members) if members.len() == 1 => {
+                visitor.visit_enum(MapAccessDeserializer::new(MapDeserializer::new(keyed(members))))
+            }
+            _ => Err(JsonError::new("expected a string or an object with one member for an enum".to_string())),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, JsonError> for Json {
+    type Deserializer = Json;
+
+    fn into_deserializer(self) -> Json {
+        self
+    }
+}
+
+fn keyed(members: Vec<(String, Json)>) -> impl Iterator<Item = (MapKey, Json)> {
+    members.into_iter().map(|(key, value)| (MapKey(key), value))
+}
+
+/// Reads a map key out of an object member name, which holds the text of a key that was a number or boolean
+struct MapKey(String);
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, JsonError> {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for MapKey {
+    type Error = JsonError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, JsonError> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, JsonError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf option unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, JsonError> for MapKey {
+    type Deserializer = MapKey;
+
+    fn into_deserializer(self) -> MapKey {
+        self
+    }
+}
+
+/// Turns a typed value into JSON, as `Json::from_serde`
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Json;
+    type Error = JsonError;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeObject;
+
+    fn serialize_bool(self, value: bool) -> Result<Json, JsonError> {
+        Ok(Json::Bool(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<Json, JsonError> {
+        Ok(Json::Number(f64::from(value)))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<Json, JsonError> {
+        Ok(Json::Number(f64::from(value)))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<Json, JsonError> {
+        Ok(Json::Number(f64::from(value)))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<Json, JsonError> {
+        Ok(Json::Number(value as f64))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<Json, JsonError> {
+        Ok(Json::Number(f64::from(value)))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<Json, JsonError> {
+        Ok(Json::Number(f64::from(value)))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<Json, JsonError> {
+        Ok(Json::Number(f64::from(value)))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<Json, JsonError> {
+        Ok(Json::Number(value as f64))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<Json, JsonError> {
+        Ok(Json::Number(f64::from(value)))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<Json, JsonError> {
+        Ok(Json::Number(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<Json, JsonError> {
+        Ok(Json::String(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Json, JsonError> {
+        Ok(Json::String(value.to_string()))
+    }
+
+    // JSON has no bytes, so they become an array of numbers
+    fn serialize_bytes(self, value: &[u8]) -> Result<Json, JsonError> {
+        Ok(Json::Array(value.iter().map(|&byte| Json::Number(f64::from(byte))).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Json, JsonError> {
+        Ok(Json::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Json, JsonError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Json, JsonError> {
+        Ok(Json::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Json, JsonError> {
+        Ok(Json::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Json, JsonError> {
+        Ok(Json::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Json, JsonError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Json, JsonError> {
+        Ok(Json::Object(vec![(variant.to_string(), value.serialize(self)?)]))
+    }
+
+    fn serialize_seq(self, length: Option<usize>) -> Result<SerializeArray, JsonError> {
+        Ok(SerializeArray {
+            variant: None,
+            values: Vec::with_capacity(length.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, length: usize) -> Result<SerializeArray, JsonError> {
+        self.serialize_seq(Some(length))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, length: usize) -> Result<SerializeArray, JsonError> {
+        self.serialize_seq(Some(length))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        length: usize,
+    ) -> Result<SerializeArray, JsonError> {
+        Ok(SerializeArray {
+            variant: Some(variant),
+            values: Vec::with_capacity(length),
+        })
+    }
+
+    fn serialize_map(self, length: Option<usize>) -> Result<SerializeObject, JsonError> {
+        Ok(SerializeObject {
+            variant: None,
+            members: Vec::with_capacity(length.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, length: usize) -> Result<SerializeObject, JsonError> {
+        self.serialize_map(Some(length))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        length: usize,
+    ) -> Result<SerializeObject, JsonError> {
+        Ok(SerializeObject {
+            variant: Some(variant),
+            members: Vec::with_capacity(length),
+            key: None,
+        })
+    }
+}
+
+/// Wrap the value of a tuple or struct variant in an object with the variant as its only member
+fn wrap(variant: Option<&'static str>, value: Json) -> Json {
+    match variant {
+        Some(variant) => Json::Object(vec![(variant.to_string(), value)]),
+        None => value,
+    }
+}
+
+struct SerializeArray {
+    variant: Option<&'static str>,
+    values: Vec<Json>,
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Json;
+    type Error = JsonError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), JsonError> {
+        self.values.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Json, JsonError> {
+        Ok(wrap(self.variant, Json::Array(self.values)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Json;
+    type Error = JsonError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), JsonError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Json, JsonError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Json;
+    type Error = JsonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), JsonError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Json, JsonError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Json;
+    type Error = JsonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), JsonError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Json, JsonError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeObject {
+    variant: Option<&'static str>,
+    members: Vec<(String, Json)>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = Json;
+    type Error = JsonError;
+
+    // object member names are strings, so keys that are numbers or booleans are written as text
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), JsonError> {
+        self.key = Some(match key.serialize(Serializer)? {
+            Json::String(key) => key,
+            key @ (Json::Number(_) | Json::Bool(_)) => key.to_string(),
+            _ => return Err(JsonError::new("map keys must be strings, numbers or booleans".to_string())),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), JsonError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| JsonError::new("map value serialized before its key".to_string()))?;
+        self.members.push((key, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Json, JsonError> {
+        Ok(wrap(self.variant, Json::Object(self.members)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = Json;
+    type Error = JsonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, name: &'static str, value: &T) -> Result<(), JsonError> {
+        self.members.push((name.to_string(), value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Json, JsonError> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeObject {
+    type Ok = Json;
+    type Error = JsonError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, name: &'static str, value: &T) -> Result<(), JsonError> {
+        ser::SerializeStruct::serialize_field(self, name, value)
+    }
+
+    fn end(self) -> Result<Json, JsonError> {
+        ser::SerializeMap::end(self)
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read};
+
+use crate::body::RequestBody;
+use crate::errors::{BuilderError, Error, ErrorKind};
+
+/// Describes a `multipart/form-data` form, which can hold text fields and files (RFC 7578)
+pub trait ValidMultipart {
+    /// Create an empty form, with a random boundary
+    fn new() -> Self;
+
+    /// Add a text field (example: `name`, `John`)
+    fn text(&mut self, name: String, value: String) -> &mut Self;
+
+    /// Add a file held in memory (example: `avatar`, `me.png`, `image/png` and the bytes of the image)
+    fn file(&mut self, name: String, filename: String, content_type: String, contents: Vec<u8>) -> &mut Self;
+
+    /// Add a file read from `reader` as the request is sent, which is `length` bytes long if that is known (example:
+    /// `upload`, `backup.tar`, `application/x-tar` and an open `File`)
+    fn stream<R: Read + Send + 'static>(
+        &mut self,
+        name: String,
+        filename: String,
+        content_type: String,
+        reader: R,
+        length: Option<u64>,
+    ) -> &mut Self;
+}
+
+/// A part of a form: its headers (which the boundary goes before), and its contents
+#[derive(Clone, Debug)]
+struct Part {
+    headers: String,
+    body: RequestBody,
+}
+
+/// A `multipart/form-data` form, which is sent as the body of a request with `Builder::multipart`
+///
+/// If any of the files are read from a source, the form is read as the request is sent rather than being put together
+/// in memory first.
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::builder::{Builder, ValidBuilder};
+/// use quickhttp::multipart::{Multipart, ValidMultipart};
+///
+/// let mut form = Multipart::new();
+/// form.text("name".to_string(), "John".to_string()).file(
+///     "avatar".to_string(),
+///     "me.png".to_string(),
+///     "image/png".to_string(),
+///     vec![0x89, b'P', b'N', b'G'],
+/// );
+///
+/// let request = Builder::new()
+///     .uri("http://example.com/profile".to_string())
+///     .method("POST".to_string())
+///     .multipart(form)
+///     .build()
+///     .unwrap();
+///
+/// assert!(request.headers["Content-Type"].starts_with("multipart/form-data; boundary="));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Multipart {
+    /// The boundary between parts, which must not appear in any of them (a random one is generated by `new`)
+    pub boundary: String,
+
+    parts: Vec<Part>,
+
+    /// The error from an invalid part, returned by `Builder::build` (the part is left out)
+    error: Option<BuilderError>,
+}
+
+impl Default for Multipart {
+    fn default() -> Multipart {
+        Multipart::new()
+    }
+}
+
+impl Multipart {
+    /// The `Content-Type` header for the form, which gives its boundary
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    fn add(&mut self, name: &str, file: Option<(&str, &str)>, body: RequestBody) {
+        // unlike the name and filename, the content type is not quoted, so a line break in it cannot be escaped
+        if let Some((_, content_type)) = file.filter(|(_, content_type)| content_type.contains(['\r', '\n'])) {
+            self.error.get_or_insert(BuilderError::with_kind(
+                ErrorKind::InvalidRequest,
+                format!("invalid content type for multipart part {:?}: {:?}", name, content_type),
+            ));
+            return;
+        }
+
+        let mut headers = format!("Content-Disposition: form-data; name=\"{}\"", escape(name));
+        if let Some((filename, content_type)) = file {
+            headers.push_str(&format!(
+                "; filename=\"{}\"\r\nContent-Type: {}",
+                escape(filename),
+                content_type
+            ));
+        }
+        headers.push_str("\r\n\r\n");
+
+        self.parts.push(Part { headers, body });
+    }
+
+    /// The error from the first invalid part that was added, if any
+    pub(crate) fn error(&self) -> Option<&BuilderError> {
+        self.error.as_ref()
+    }
+
+    /// The form as a request body, which is read from the parts' sources if any of them have one
+    pub(crate) fn into_body(self) -> RequestBody {
+        let head = |part: &Part| format!("--{}\r\n{}", self.boundary, part.headers);
+        let end = format!("--{}--\r\n", self.boundary);
+
+        if self.parts.iter().all(|part| part.body.as_bytes().is_some()) {
+            let mut body = Vec::new();
+            for part in &self.parts {
+                body.extend_from_slice(head(part).as_bytes());
+                body.extend_from_slice(part.body.as_bytes().unwrap_or_default());
+                body.extend_from_slice(b"\r\n");
+            }
+            body.extend_from_slice(end.as_bytes());
+            return RequestBody::from(body);
+        }
+
+        let mut length = Some(end.len() as u64);
+        let mut readers: VecDeque<Box<dyn Read + Send>> = VecDeque::new();
+
+        for part in self.parts {
+            let head = head(&part);
+            let body_length = part.body.len();
+            length = match (length, body_length) {
+                (Some(length), Some(body)) => Some(length + head.len() as u64 + body + 2),
+                _ => None,
+            };
+
+            readers.push_back(Box::new(io::Cursor::new(head.into_bytes())));
+            match body_length {
+                // a source that gives more than it said it would is cut short, to keep the parts apart
+                Some(body_length) => readers.push_back(Box::new(part.body.into_reader().take(body_length))),
+                None => readers.push_back(part.body.into_reader()),
+            }
+            readers.push_back(Box::new(&b"\r\n"[..]));
+        }
+        readers.push_back(Box::new(io::Cursor::new(end.into_bytes())));
+
+        RequestBody::reader(Concat { readers }, length)
+    }
+}
+
+/// Escape a field name or filename for a quoted header parameter, the way browsers do
+fn escape(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Generate a boundary that is very unlikely to appear in any part
+fn boundary() -> String {
+    let random = |seed: u64| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(seed);
+        hasher.finish()
+    };
+
+    format!("------------------------{:016x}{:016x}", random(0), random(1))
+}
+
+/// Reads from each reader in turn, until they have all been read to the end
+struct Concat {
+    readers: VecDeque<Box<dyn Read + Send>>,
+}
+
+impl Read for Concat {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(reader) = self.readers.front_mut() {
+            match reader.read(buf)? {
+                0 if !buf.is_empty() => {
+                    self.readers.pop_front();
+                }
+                read => return Ok(read),
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+impl ValidMultipart for Multipart {
+    fn new() -> Multipart {
+        Multipart {
+            boundary: boundary(),
+            parts: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn text(&mut self, name: String, value: String) -> &mut Self {
+        self.add(&name, None, RequestBody::from(value));
+        self
+    }
+
+    fn file(&mut self, name: String, filename: String, content_type: String, contents: Vec<u8>) -> &mut Self {
+        self.add(&name, Some((&filename, &content_type)), RequestBody::from(contents));
+        self
+    }
+
+    fn stream<R: Read + Send + 'static>(
+        &mut self,
+        name: String,
+        filename: String,
+        content_type: String,
+        reader: R,
+        length: Option<u64>,
+    ) -> &mut Self {
+        self.add(&name, Some((&filename, &content_type)), RequestBody::reader(reader, length));
+        self
+    }
+}
@@ -0,0 +1,139 @@
+use crate::body::RequestBody;
+use crate::errors::{Error, ErrorKind, RequestError};
+use crate::request::Request;
+use crate::response::ResponseHead;
+use crate::status_code::StatusCode;
+use crate::url::Url;
+
+/// Headers that carry credentials, which are not passed on when a redirect leads to a different host
+const SENSITIVE_HEADERS: [&str; 3] = ["Authorization", "Proxy-Authorization", "Cookie"];
+
+/// Headers that describe the body, which are dropped along with it when a redirect changes the method to GET
+const BODY_HEADERS: [&str; 4] = ["Content-Length", "Content-Type", "Content-Encoding", "Transfer-Encoding"];
+
+/// A redirect that was followed on the way to a response
+#[derive(Clone, Debug)]
+pub struct Redirect {
+    /// The status code of the redirect (example: `301`)
+    pub status_code: StatusCode,
+
+    /// The URL that answered with the redirect
+    pub from: Url,
+
+    /// The URL that was redirected to, with a relative `Location` resolved against `from`
+    pub to: Url,
+}
+
+impl Request {
+    /// Send the request with `send`, following any redirects, and record the redirects on the final response
+    pub(crate) fn follow_redirects<R: ResponseHead>(
+        &self,
+        mut send: impl FnMut(&Request) -> Result<R, RequestError>,
+    ) -> Result<R, RequestError> {
+        let mut request = self.clone();
+        let mut redirects = Vec::new();
+
+        loop {
+            let mut response = send(&request)?;
+
+            match request.redirect(&response, redirects.len())? {
+                Some((next, redirect)) => {
+                    redirects.push(redirect);
+                    request = next;
+                }
+                None => {
+                    response.set_redirects(redirects);
+                    return Ok(response);
+                }
+            }
+        }
+    }
+
+    /// The request to send next, if `response` is a redirect that should be followed
+    ///
+    /// `hops` is how many redirects have already been followed. Following more than `max_redirects` is an error of
+    /// kind `TooManyRedirects`.
+    pub(crate) fn redirect(
+        &self,
+        response: &impl ResponseHead,
+        hops: usize,
+    ) -> Result<Option<(Request, Redirect)>, RequestError> {
+        let status_code = response.status_code().clone();
+        if self.max_redirects == 0 || !is_redirect(&status_code) {
+            return Ok(None);
+        }
+
+        let location = match response.headers().get("Location") {
+            Some(location) => location.trim(),
+            // without a Location there is nowhere to go, so the redirect itself is the response
+            None => return Ok(None),
+        };
+
+        if hops >= self.max_redirects {
+            return Err(RequestError::with_kind(
+                ErrorKind::TooManyRedirects,
+                format!("stopped after {} redirects", hops),
+            ));
+        }
+
+        let from = self.url()?;
+        let to = from.join(location)?;
+
+        let host = match to.host() {
+            Some(host) if !host.is_empty() => host.to_string(),
+            _ => return Err(RequestError::with_kind(ErrorKind::InvalidUrl, format!("redirect has no host: {}", to))),
+        };
+        let port = match to.port_or_default() {
+            Some(port) => port,
+            None => return Err(RequestError::with_kind(ErrorKind::InvalidUrl, format!("redirect has no port: {}", to))),
+        };
+
+        let mut next = self.clone();
+
+        // 303 always becomes a GET, and so (for historical reasons) do 301 and 302 in answer to a POST
+        let to_get = match status_code {
+            StatusCode::SeeOther => !self.method.eq_ignore_ascii_case("HEAD"),
+            StatusCode::MovedPermanently | StatusCode::Found => self.method.eq_ignore_ascii_case("POST"),
+            _ => false,
+        };
+        if to_get {
+            next.method = "GET".to_string();
+            next.body = RequestBody::empty();
+            next.headers
+                .retain(|key, _| !BODY_HEADERS.iter().any(|header| key.eq_ignore_ascii_case(header)));
+        }
+
+        // credentials are only for the server they were meant for
+        let same_origin = to.scheme() == self.scheme && host.eq_ignore_ascii_case(&self.host) && port == self.port;
+        if !same_origin {
+            next.headers
+                .retain(|key, _| !SENSITIVE_HEADERS.iter().any(|header| key.eq_ignore_ascii_case(header)));
+        }
+
+        next.scheme = to.scheme().to_string();
+        next.host = host;
+        next.port = port;
+        next.path = to.request_target();
+
+        Ok(Some((
+            next,
+            Redirect {
+                status_code,
+                from,
+                to,
+            },
+        )))
+    }
+}
+
+/// Whether a status code is a redirect that can be followed
+fn is_redirect(status_code: &StatusCode) -> bool {
+    matches!(
+        status_code,
+        StatusCode::MovedPermanently
+            | StatusCode::Found
+            | StatusCode::SeeOther
+            | StatusCode::TemporaryRedirect
+            | StatusCode::PermanentRedirect
+    )
+}
@@ -1,9 +1,21 @@
-use std::collections::HashMap;
-use std::future::Future;
-use std::net::ToSocketAddrs;
-use std::{io::{Read, Write}, net::TcpStream};
-use crate::StatusCode;
-use crate::{errors::{Error, RequestError}, response::Response};
+use std::io::{self, BufRead, BufReader, Write};
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::wire;
+use crate::transport::{self, Connector, Stream, TcpConnector, TlsConnector};
+use crate::client::{Client, ValidClient};
+use crate::headers::HeaderMap;
+use crate::body::RequestBody;
+use crate::inflate;
+use crate::proxy::{self, Proxy};
+use crate::resolver::Resolver;
+use crate::retry::RetryPolicy;
+use crate::socks;
+use crate::url::Url;
+use crate::{errors::{BuilderError, Error, ErrorKind, RequestError, ResponseError, TimeoutKind}, response::Response};
+use crate::response::{ResponseBody, StreamingResponse};
 
 /// Describes a valid request
 pub trait ValidRequest {
@@ -11,7 +23,7 @@ pub trait ValidRequest {
     fn new(
         method: String,
         path: String,
-        headers: HashMap<String, String>,
+        headers: HeaderMap,
         body: String,
         host: String,
         port: u16,
@@ -26,7 +38,7 @@ trait HeadersToString {
     fn headers_to_string(&self) -> String;
 }
 
-impl HeadersToString for HashMap<String, String> {
+impl HeadersToString for HeaderMap {
     fn headers_to_string(&self) -> String {
         let mut headers = String::new();
         for (key, value) in self.iter() {
@@ -41,23 +53,77 @@ impl HeadersToString for HashMap<String, String> {
 pub struct Request {
     pub method: String,
     pub path: String,
-    pub headers: HashMap<String, String>,
-    pub body: String,
+    pub headers: HeaderMap,
+    pub body: RequestBody,
     pub host: String,
     pub port: u16,
     pub http_version: String,
+    /// The scheme of the request, `http` or `https`
+    pub scheme: String,
+    /// Opens the connection to the server (a plain TCP connection if not set)
+    pub connector: Option<Arc<dyn Connector>>,
+    /// Secures the connection for `https` requests (`rustls`, with the `tls` feature, if not set)
+    pub tls_connector: Option<Arc<dyn TlsConnector>>,
+    /// How long to wait for the connection to be established (`5` seconds if not set)
+    pub connect_timeout: Option<Duration>,
+    /// How long to wait for the server to send anything (no limit if not set)
+    pub read_timeout: Option<Duration>,
+    /// How long a write of the request may block (no limit if not set)
+    pub write_timeout: Option<Duration>,
+    /// The deadline for the whole request, from connecting to reading the last byte of the body (no limit if not set)
+    pub timeout: Option<Duration>,
+    /// The most redirects to follow before giving up (`0` returns redirects as they are)
+    pub max_redirects: usize,
+    /// Whether to ask for a compressed response, and decompress the body according to its `Content-Encoding`
+    ///
+    /// A body that decompresses to more than 128 MiB is an error, rather than being decompressed into memory.
+    pub decompress: bool,
+    /// When to send the request again if it fails (the client's policy, or a single attempt, if not set)
+    pub retry: Option<RetryPolicy>,
+    /// The proxies to send the request through (a direct connection if not set)
+    pub proxy: Option<Proxy>,
+    /// Looks up the addresses of the server, or of the proxy (the system resolver if not set)
+    pub resolver: Option<Arc<dyn Resolver>>,
 }
 
 impl Request {
-    fn fail(&self, message: &str) -> RequestError {
-        RequestError::new(format!("RequestError: {}", message))
+    fn fail(&self, kind: ErrorKind, message: &str) -> RequestError {
+        RequestError::with_kind(kind, message.to_string())
+    }
+
+    /// Send the request through a client, reusing one of its pooled connections if possible
+    pub fn send_with(&self, client: &Client) -> Result<Response, RequestError> {
+        client.send(self)
+    }
+
+    /// Send the request, returning as soon as the status and headers of the response have been read
+    ///
+    /// The body is left on the connection, to be read through [`StreamingResponse::body`]. Redirects and retries are
+    /// handled as with `send`.
+    pub fn send_streaming(&self) -> Result<StreamingResponse, RequestError> {
+        let timeouts = self.timeouts(None);
+
+        self.follow_redirects(|request| {
+            request.send_retrying(request.retry.as_ref(), &timeouts, |request| request.open(&timeouts))
+        })
     }
 
     /// Send the request asynchronously, returning a future
-    pub fn async_send(&self) -> impl Future<Output = Result<Response, RequestError>> + '_ {
-        async move {
-            self.send()
-        }
+    ///
+    /// With the `tokio` feature, plain `http` requests are sent over a tokio `TcpStream` without blocking the executor,
+    /// and anything that needs a (synchronous) connector is sent on tokio's blocking thread pool. Without it, this blocks
+    /// while the request is sent, just like `send`.
+    #[cfg(feature = "tokio")]
+    pub async fn async_send(&self) -> Result<Response, RequestError> {
+        crate::asynchronous::send(self).await
+    }
+
+    /// Send the request asynchronously, returning a future
+    ///
+    /// Without the `tokio` feature this blocks while the request is sent, just like `send`.
+    #[cfg(not(feature = "tokio"))]
+    pub async fn async_send(&self) -> Result<Response, RequestError> {
+        self.send()
     }
 }
 
@@ -66,7 +132,7 @@ impl ValidRequest for Request {
     fn new(
         method: String,
         path: String,
-        headers: HashMap<String, String>,
+        headers: HeaderMap,
         body: String,
         host: String,
         port: u16,
@@ -76,89 +142,561 @@ impl ValidRequest for Request {
             method,
             path,
             headers,
-            body,
+            body: RequestBody::from(body),
             host,
             port,
             http_version,
+            scheme: "http".to_string(),
+            connector: None,
+            tls_connector: None,
+            connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            timeout: None,
+            max_redirects: 10,
+            decompress: true,
+            retry: None,
+            proxy: None,
+            resolver: None,
         }
     }
 
     fn send(&self) -> Result<Response, RequestError> {
-        // no propagation of errors, just return a new error using self.fail and the match statement
-        
-        // connect to server, with 5s timeout, handle errors
+        let timeouts = self.timeouts(None);
 
-        let addr = format!("{}:{}", self.host, self.port).to_socket_addrs();
+        self.follow_redirects(|request| {
+            request.send_retrying(request.retry.as_ref(), &timeouts, |request| request.send_once(&timeouts))
+        })
+    }
+}
 
-        let addr = match addr {
-            Ok(mut addr) => match addr.next() {
-                Some(addr) => addr,
-                None => return Err(self.fail("could not resolve address")),
-            },
-            Err(_) => return Err(self.fail("could not resolve address")),
+/// The timeouts for sending one request, with the overall timeout turned into a deadline
+#[derive(Clone, Debug)]
+pub(crate) struct Timeouts {
+    pub(crate) connect: Option<Duration>,
+    pub(crate) read: Option<Duration>,
+    pub(crate) write: Option<Duration>,
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl Timeouts {
+    /// Whether the deadline for the request has passed
+    pub(crate) fn expired(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// How long an operation may take: `timeout`, cut short if the deadline comes first
+    pub(crate) fn limit(&self, timeout: Option<Duration>) -> Option<Duration> {
+        let remaining = self
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+        let limit = match (timeout, remaining) {
+            (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+            (timeout, remaining) => timeout.or(remaining),
+        };
+
+        // sockets treat a zero timeout as an error, rather than as expiring straight away
+        limit.map(|limit| limit.max(Duration::from_millis(1)))
+    }
+
+    /// How long to wait for a TCP connection to be established, which is limited to 5 seconds if no timeout is set
+    pub(crate) fn connect_limit(&self) -> Duration {
+        self.limit(self.connect).unwrap_or(TcpConnector::new().connect_timeout)
+    }
+
+    /// Report a timeout as the deadline expiring if that is what cut it short
+    pub(crate) fn classify(&self, mut error: RequestError) -> RequestError {
+        if matches!(error.kind, ErrorKind::Timeout(_)) && self.expired() {
+            error.kind = ErrorKind::Timeout(TimeoutKind::Deadline);
+        }
+        error
+    }
+
+    /// Turn an error from writing the request into a request error
+    pub(crate) fn write_failed(&self, error: io::Error) -> RequestError {
+        self.classify(RequestError::io(ErrorKind::Io, TimeoutKind::Write, "could not write request", error))
+    }
+
+    /// Turn an error from reading the response into a request error, reporting malformed or truncated data as a
+    /// `ResponseError`
+    pub(crate) fn read_failed(&self, message: &str, error: io::Error) -> RequestError {
+        match error.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                ResponseError::new(format!("{}: {}", message, error))
+                    .with_source(error)
+                    .into()
+            }
+            _ => self.classify(RequestError::io(ErrorKind::Io, TimeoutKind::Read, message, error)),
+        }
+    }
+}
+
+/// A request that could not be sent over a connection, with whether the server closed it without answering at all
+///
+/// A connection that is closed before anything is read back was most likely closed by the server while it sat idle,
+/// so the request can be sent again over a new one.
+#[derive(Debug)]
+pub(crate) struct Failure {
+    pub(crate) error: RequestError,
+    pub(crate) unanswered: bool,
+}
+
+impl From<RequestError> for Failure {
+    fn from(error: RequestError) -> Failure {
+        Failure { error, unanswered: false }
+    }
+}
+
+impl From<ResponseError> for Failure {
+    fn from(error: ResponseError) -> Failure {
+        Failure::from(RequestError::from(error))
+    }
+}
+
+impl From<Failure> for RequestError {
+    fn from(failure: Failure) -> RequestError {
+        failure.error
+    }
+}
+
+/// Whether an error means the other end closed the connection (rather than, say, taking too long to answer)
+fn closed(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+/// Reads from a connection, applying the read timeout (and the deadline) to every read that has to wait for the server
+struct TimedReader<'a, S: Stream> {
+    stream: &'a mut BufReader<S>,
+    timeouts: &'a Timeouts,
+    /// How many bytes have been read from the connection through this reader
+    received: usize,
+}
+
+impl<S: Stream> Read for TimedReader<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<S: Stream> BufRead for TimedReader<'_, S> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.stream.buffer().is_empty() {
+            if self.timeouts.expired() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "request deadline exceeded"));
+            }
+            self.stream
+                .get_ref()
+                .set_read_timeout(self.timeouts.limit(self.timeouts.read))?;
+        }
+        self.stream.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.received += amount;
+        self.stream.consume(amount)
+    }
+}
+
+impl Request {
+    /// The URL the request is sent to
+    pub fn url(&self) -> Result<Url, BuilderError> {
+        Url::parse(&format!("{}://{}{}", self.scheme, self.host_header(), self.path))
+    }
+
+    /// Send the request over a new connection, without following redirects
+    pub(crate) fn send_once(&self, timeouts: &Timeouts) -> Result<Response, RequestError> {
+        let stream = self.connect(timeouts)?;
+        let mut stream = BufReader::new(stream);
+
+        match self.exchange(&mut stream, false, timeouts) {
+            Ok((response, _)) => Ok(response),
+            Err(failure) => Err(failure.error),
+        }
+    }
+
+    /// Send the request over a new connection, and leave the body of the response to be read as it is used
+    fn open(&self, timeouts: &Timeouts) -> Result<StreamingResponse, RequestError> {
+        let mut stream = BufReader::new(self.connect(timeouts)?);
+
+        // the body is not decompressed as it is read, so a compressed one is not asked for
+        let (request, headers) = self.serialize(false, false);
+        let (_, parsed) = self.start(&mut stream, (&request, &headers), timeouts)?;
+
+        let framing = self.framing(&parsed)?;
+        let remote_addr = stream.get_ref().peer_addr();
+
+        Ok(StreamingResponse {
+            status_code: parsed.status_code,
+            status_code_raw: parsed.code,
+            headers: parsed.headers,
+            body: ResponseBody::new(wire::BodyReader::new(stream, framing), timeouts.clone()),
+            request_used: self.clone(),
+            redirects: Vec::new(),
+            remote_addr,
+        })
+    }
+
+    /// Work out the timeouts for sending the request, falling back to the client's for any the request does not set
+    pub(crate) fn timeouts(&self, client: Option<&Client>) -> Timeouts {
+        let timeout = self.timeout.or(client.and_then(|client| client.timeout));
+
+        Timeouts {
+            connect: self.connect_timeout.or(client.and_then(|client| client.connect_timeout)),
+            read: self.read_timeout.or(client.and_then(|client| client.read_timeout)),
+            write: self.write_timeout.or(client.and_then(|client| client.write_timeout)),
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+
+    /// Open a connection to the server, securing it with TLS for `https` requests
+    pub(crate) fn connect(&self, timeouts: &Timeouts) -> Result<Box<dyn Stream>, RequestError> {
+        self.try_connect(timeouts).map_err(|error| timeouts.classify(error))
+    }
+
+    fn try_connect(&self, timeouts: &Timeouts) -> Result<Box<dyn Stream>, RequestError> {
+        let connect_timeout = timeouts.limit(timeouts.connect);
+
+        // with a proxy, the connection is opened to the proxy rather than the server
+        let proxy = self.proxy_server();
+        let (host, port) = match proxy {
+            Some(server) => proxy::address(server),
+            None => (self.host.as_str(), self.port),
+        };
+
+        let mut stream = match &self.connector {
+            Some(connector) => connector.connect(host, port)?,
+            None => {
+                let connector = TcpConnector {
+                    connect_timeout: timeouts.connect_limit(),
+                    resolver: self.resolver.clone(),
+                };
+                connector.connect(host, port)?
+            }
+        };
+
+        if timeouts.expired() {
+            return Err(self.fail(ErrorKind::Timeout(TimeoutKind::Deadline), "request deadline exceeded"));
+        }
+
+        // the TLS handshake, and any handshake with a proxy, counts towards the connect timeout
+        if proxy.is_some() || self.scheme == "https" {
+            let set_timeouts = |timeout| {
+                stream.set_read_timeout(timeout)?;
+                stream.set_write_timeout(timeout)
+            };
+            if let Err(error) = set_timeouts(connect_timeout) {
+                return Err(RequestError::io(ErrorKind::Io, TimeoutKind::Connect, "could not set timeouts", error));
+            }
+        }
+
+        match proxy {
+            Some(server) if proxy::is_socks(server) => socks::connect(&mut stream, &self.host, self.port, server, self.resolver.as_deref())?,
+            Some(server) if self.scheme == "https" => proxy::tunnel(&mut stream, &self.authority(), server)?,
+            _ => {}
+        }
+
+        match self.scheme.as_str() {
+            "http" => Ok(stream),
+            "https" => {
+                match &self.tls_connector {
+                    Some(tls_connector) => tls_connector.connect(&self.host, stream),
+                    None => transport::default_tls_connector()?.connect(&self.host, stream),
+                }
+            }
+            scheme => Err(self.fail(ErrorKind::InvalidUrl, &format!("unsupported scheme: {}", scheme))),
+        }
+    }
+
+    /// The proxy the request is sent through, if it has one for the request's scheme and host
+    pub(crate) fn proxy_server(&self) -> Option<&Url> {
+        self.proxy.as_ref()?.server(&self.scheme, &self.host)
+    }
+
+    /// The host and port of the server, as a `CONNECT` request asks for them (example: `example.com:443`)
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// The value of the `Host` header, which only includes the port if it is not the default for the scheme
+    fn host_header(&self) -> String {
+        let default_port = match self.scheme.as_str() {
+            "https" => 443,
+            _ => 80,
+        };
+
+        // IPv6 addresses are written in brackets
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
         };
 
-        let stream = TcpStream::connect_timeout(&addr, std::time::Duration::from_secs(5));
+        if self.port == default_port {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+
+    /// Write the request to an open connection and read back the response
+    ///
+    /// The body is framed by its `Content-Length`, chunked encoding, or the server closing the connection, so nothing past
+    /// the end of the response is read. Alongside the response, returns whether the connection can be reused.
+    ///
+    /// Unless `keep_alive` is set, the server is asked to close the connection (if the request does not say otherwise).
+    pub(crate) fn exchange<S: Stream>(
+        &self,
+        stream: &mut BufReader<S>,
+        keep_alive: bool,
+        timeouts: &Timeouts,
+    ) -> Result<(Response, bool), Failure> {
+        let (request, headers) = self.serialize(keep_alive, self.decompress);
+        let (head, parsed) = self.start(stream, (&request, &headers), timeouts)?;
+
+        let framing = self.framing(&parsed)?;
 
-        let mut stream = match stream {
-            Ok(stream) => stream,
-            Err(_) => return Err(self.fail("could not connect to server")),
+        let mut stream = TimedReader { stream, timeouts, received: 0 };
+        let mut body_reader = wire::BodyReader::new(&mut stream, framing);
+        body_reader.record_raw();
+
+        let mut decoded = Vec::new();
+        if let Err(error) = body_reader.read_to_end(&mut decoded) {
+            return Err(timeouts.read_failed("could not read response body", error).into());
+        }
+
+        let body = wire::Body {
+            decoded,
+            raw: body_reader.take_raw(),
+            trailers: body_reader.trailers(),
         };
 
+        let remote_addr = stream.stream.get_ref().peer_addr();
+        Ok(self.finish(&headers, head, parsed, framing, body, remote_addr)?)
+    }
+
+    /// Write the request (its serialized head, then the body) to an open connection, and read the head of the final
+    /// response
+    fn start<S: Stream>(
+        &self,
+        stream: &mut BufReader<S>,
+        (head, headers): (&str, &HeaderMap),
+        timeouts: &Timeouts,
+    ) -> Result<(Vec<u8>, wire::Head), Failure> {
+        if self.body.is_spent() {
+            return Err(self.fail(ErrorKind::InvalidRequest, "a body read from a source can only be sent once").into());
+        }
+
+        let write = |stream: &mut S| {
+            if timeouts.expired() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "request deadline exceeded"));
+            }
+            stream.set_write_timeout(timeouts.limit(timeouts.write))?;
+
+            let mut stream = io::BufWriter::new(stream);
+            stream.write_all(head.as_bytes())?;
+            self.body.write_to(&mut stream, sends_chunked(headers))?;
+            stream.flush()
+        };
+        if let Err(error) = write(stream.get_mut()) {
+            let unanswered = closed(&error);
+            return Err(Failure {
+                error: timeouts.write_failed(error),
+                unanswered,
+            });
+        }
+
+        let mut stream = TimedReader { stream, timeouts, received: 0 };
+
+        // skip over any interim (1xx) responses, such as 100 Continue
+        loop {
+            let head = match wire::read_head(&mut stream) {
+                Ok(head) => head,
+                Err(error) => {
+                    let unanswered = stream.received == 0 && closed(&error);
+                    return Err(Failure {
+                        error: timeouts.read_failed("could not read response", error),
+                        unanswered,
+                    });
+                }
+            };
+
+            if let Some(parsed) = final_head(&head)? {
+                return Ok((head, parsed));
+            }
+        }
+    }
+
+    /// The head of the request as it is written to the connection (the body follows it), along with the headers it is
+    /// sent with
+    ///
+    /// A body of unknown length is sent chunked, unless the request sets its own `Content-Length` or
+    /// `Transfer-Encoding`. Unless `keep_alive` is set, the server is asked to close the connection (if the request does not say otherwise).
+    /// With `accept_compressed`, the server is told it may compress the body. A plain `http` request sent through an
+    /// HTTP proxy has the whole URL as its target, and the proxy's credentials.
+    pub(crate) fn serialize(&self, keep_alive: bool, accept_compressed: bool) -> (String, HeaderMap) {
         let mut headers = self.headers.clone();
 
-        if !headers.contains_key("Content-Length") {
-            let content_length = self.body.len();
-            headers.insert("Content-Length".to_string(), content_length.to_string());
+        if !keep_alive && !headers.contains_key("Connection") {
+            headers.insert("Connection".to_string(), "close".to_string());
+        }
+
+        if accept_compressed && !headers.contains_key("Accept-Encoding") {
+            headers.insert("Accept-Encoding".to_string(), "gzip, deflate".to_string());
+        }
+
+        if !headers.contains_key("Content-Length") && !headers.contains_key("Transfer-Encoding") {
+            match self.body.len() {
+                Some(content_length) => headers.insert("Content-Length".to_string(), content_length.to_string()),
+                None => headers.insert("Transfer-Encoding".to_string(), "chunked".to_string()),
+            };
+        }
+
+        // a request forwarded by a proxy names the whole URL it is for (RFC 9112, section 3.2.2)
+        let mut target = self.path.clone();
+        let forwarded = self.proxy_server().filter(|server| !proxy::is_socks(server));
+        if let (Some(server), "http") = (forwarded, self.scheme.as_str()) {
+            target = format!("{}://{}{}", self.scheme, self.host_header(), self.path);
+
+            if let Some(authorization) = proxy::authorization(server) {
+                if !headers.contains_key("Proxy-Authorization") {
+                    headers.insert("Proxy-Authorization".to_string(), authorization);
+                }
+            }
         }
 
         let request = format!(
-            "{} {} HTTP/{}\r\nHost: {}\r\n{}\r\n{}",
-            self.method, self.path, self.http_version, self.host, headers.headers_to_string(), self.body
+            "{} {} HTTP/{}\r\nHost: {}\r\n{}\r\n",
+            self.method, target, self.http_version, self.host_header(), headers.headers_to_string()
         );
 
-        let _ = match stream.write(request.as_bytes()) {
-            Ok(_) => (),
-            Err(_) => return Err(self.fail("could not write request")),
-        };
+        (request, headers)
+    }
+
+    /// Work out how the body of a response is framed
+    pub(crate) fn framing(&self, parsed: &wire::Head) -> Result<wire::Framing, RequestError> {
+        match wire::Framing::of(&self.method, parsed.code, &parsed.headers) {
+            Ok(framing) => Ok(framing),
+            Err(message) => Err(ResponseError::new(message).into()),
+        }
+    }
 
-        let mut response = Vec::new();
+    /// Put together the response once it has been read, and work out whether the connection can be reused
+    ///
+    /// Unless `decompress` is turned off, a compressed body is decompressed, and the `Content-Encoding` and
+    /// `Content-Length` headers (which describe the compressed body) are removed.
+    pub(crate) fn finish(
+        &self,
+        sent_headers: &HeaderMap,
+        head: Vec<u8>,
+        mut parsed: wire::Head,
+        framing: wire::Framing,
+        mut body: wire::Body,
+        remote_addr: Option<SocketAddr>,
+    ) -> Result<(Response, bool), RequestError> {
+        let mut raw_response = head;
+        raw_response.extend_from_slice(b"\r\n\r\n");
+        raw_response.extend_from_slice(&body.raw);
 
-        let _ = match stream.read_to_end(&mut response) {
-            Ok(_) => (),
-            Err(_) => return Err(self.fail("could not read response")),
+        let reusable = framing != wire::Framing::Close
+            && keeps_alive(&parsed.version, &parsed.headers)
+            && keeps_alive(&self.http_version, sent_headers);
+
+        if self.decompress && !body.decoded.is_empty() {
+            if let Some(decoded) = decompress(&parsed.headers, &body.decoded)? {
+                body.decoded = decoded;
+                parsed.headers.remove("Content-Encoding");
+                parsed.headers.remove("Content-Length");
+            }
+        }
+
+        Ok((
+            Response {
+                raw_response,
+                status_code: parsed.status_code,
+                status_code_raw: parsed.code,
+                headers: parsed.headers,
+                body: body.decoded,
+                trailers: body.trailers,
+                redirects: Vec::new(),
+                request_used: self.clone(),
+                remote_addr,
+            },
+            reusable,
+        ))
+    }
+}
+
+/// Decompress a body according to its `Content-Encoding`, returning `None` if it is not compressed, or is compressed
+/// in a way that is not supported
+fn decompress(headers: &HeaderMap, body: &[u8]) -> Result<Option<Vec<u8>>, ResponseError> {
+    let encodings = match headers.joined("Content-Encoding") {
+        Some(encodings) => encodings,
+        None => return Ok(None),
+    };
+
+    // the codings were applied in the order they are listed, so they are undone in reverse
+    let mut decoded = body.to_vec();
+    for encoding in encodings.rsplit(',') {
+        decoded = match inflate::decode(encoding, &decoded, inflate::MAX_OUTPUT) {
+            Some(Ok(decoded)) => decoded,
+            Some(Err(message)) => {
+                return Err(ResponseError::new(format!("could not decode {} body: {}", encoding.trim(), message)))
+            }
+            None => return Ok(None),
         };
+    }
 
-        // parse the response
-        let response = String::from_utf8(response).unwrap();
+    Ok(Some(decoded))
+}
 
-        let mut parts = response.split("\r\n\r\n");
-        let headers = parts.next().unwrap();
-        let body = parts.next().unwrap();
+/// Whether a request sent with these headers has a chunked body, which is when chunked is the final transfer coding
+pub(crate) fn sends_chunked(headers: &HeaderMap) -> bool {
+    headers
+        .joined("Transfer-Encoding")
+        .is_some_and(|codings| codings.rsplit(',').next().unwrap_or("").trim().eq_ignore_ascii_case("chunked"))
+}
 
-        let mut headers = headers.split("\r\n");
-        let status_line = headers.next().unwrap();
-        let status_code = status_line.split(" ").nth(1).unwrap().parse::<u16>().unwrap();
+/// Parse a response head, returning `None` for an interim (1xx) response that should be skipped
+///
+/// `101 Switching Protocols` is final, since nothing else follows it over HTTP.
+pub(crate) fn final_head(head: &[u8]) -> Result<Option<wire::Head>, ResponseError> {
+    let parsed = wire::parse_head(head)?;
 
-        let headers = headers.map(|header| {
-            let mut parts = header.split(": ");
-            let key = parts.next().unwrap();
-            let value = parts.next().unwrap();
-            (key.to_string(), value.to_string())
-        });
+    if (100..200).contains(&parsed.code) && parsed.code != 101 {
+        Ok(None)
+    } else {
+        Ok(Some(parsed))
+    }
+}
 
-        let mut headers_map = HashMap::new();
+/// Whether the connection stays open after a response, going by the `Connection` header and HTTP version (RFC 9112, section 9.3)
+fn keeps_alive(http_version: &str, headers: &HeaderMap) -> bool {
+    let connection = headers.joined("Connection").map(|value| value.to_ascii_lowercase());
 
-        for (key, value) in headers {
-            headers_map.insert(key, value);
-        }
+    let has_option = |option: &str| {
+        connection
+            .as_ref()
+            .is_some_and(|value| value.split(',').any(|token| token.trim() == option))
+    };
 
-        Ok(Response {
-            raw_response: response.clone(),
-            status_code: StatusCode::from_u16(status_code).unwrap(),
-            headers: headers_map,
-            body: body.to_string(),
-            request_used: self.clone(),
-        })
+    if has_option("close") {
+        return false;
     }
-}
\ No newline at end of file
+
+    http_version != "1.0" || has_option("keep-alive")
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::errors::{Error, ErrorKind, RequestError, TimeoutKind};
+
+/// Describes a way of looking up the addresses of a host (for example, the system resolver, or a service discovery
+/// lookup)
+pub trait Resolver: Send + Sync {
+    /// Look up the addresses of `host`, as socket addresses with the given port, in the order they should be tried
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, RequestError>;
+}
+
+impl core::fmt::Debug for dyn Resolver {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Resolver")
+    }
+}
+
+/// The default resolver, which asks the operating system (`getaddrinfo`, so `/etc/hosts` is used too)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, RequestError> {
+        match (host, port).to_socket_addrs() {
+            Ok(addrs) => Ok(addrs.collect()),
+            Err(error) => Err(RequestError::io(ErrorKind::Dns, TimeoutKind::Connect, "could not resolve address", error)),
+        }
+    }
+}
+
+/// A resolver that gives fixed addresses for some hosts, like curl's `--resolve`, and passes every other host on to
+/// another resolver
+///
+/// # Examples
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use quickhttp::builder::{Builder, ValidBuilder};
+/// use quickhttp::resolver::StaticResolver;
+///
+/// let mut resolver = StaticResolver::new();
+/// resolver.insert("example.com", vec!["127.0.0.1".parse().unwrap()]);
+///
+/// let request = Builder::new()
+///     .uri("http://example.com/".to_string())
+///     .resolver(Arc::new(resolver))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticResolver {
+    /// The addresses of each host, by lowercase host name (example: `example.com` at `127.0.0.1`)
+    pub hosts: HashMap<String, Vec<IpAddr>>,
+
+    /// The resolver for hosts that are not listed, or `None` for them to fail to resolve (example: `SystemResolver`)
+    pub fallback: Option<Arc<dyn Resolver>>,
+}
+
+impl StaticResolver {
+    /// Create a static resolver with no hosts, which resolves every host with the system resolver
+    pub fn new() -> StaticResolver {
+        StaticResolver {
+            hosts: HashMap::new(),
+            fallback: Some(Arc::new(SystemResolver)),
+        }
+    }
+
+    /// Pin `host` to the given addresses, replacing any it already had (example: `example.com`, `[127.0.0.1]`)
+    pub fn insert(&mut self, host: &str, addrs: Vec<IpAddr>) -> &mut Self {
+        self.hosts.insert(normalize(host), addrs);
+        self
+    }
+}
+
+impl Default for StaticResolver {
+    fn default() -> StaticResolver {
+        StaticResolver::new()
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, RequestError> {
+        if let Some(addrs) = self.hosts.get(&normalize(host)) {
+            return Ok(addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect());
+        }
+
+        match &self.fallback {
+            Some(fallback) => fallback.resolve(host, port),
+            None => Err(RequestError::with_kind(ErrorKind::Dns, format!("no addresses are set for host: {}", host))),
+        }
+    }
+}
+
+/// The addresses looked up for each host and port, with when they were looked up
+type Cache = HashMap<(String, u16), (Instant, Vec<SocketAddr>)>;
+
+/// A resolver that remembers the addresses another resolver gave for each host, for a fixed time
+///
+/// Failed lookups are not remembered. Clones of a caching resolver share the same cache.
+///
+/// # Examples
+/// ```rust
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// use quickhttp::client::{Client, ValidClient};
+/// use quickhttp::resolver::{CachingResolver, SystemResolver};
+///
+/// let mut client = Client::new();
+/// client.resolver(Arc::new(CachingResolver::new(Arc::new(SystemResolver), Duration::from_secs(60))));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CachingResolver {
+    /// The resolver that hosts are looked up with when they are not in the cache (example: `SystemResolver`)
+    pub inner: Arc<dyn Resolver>,
+
+    /// How long the addresses of a host are remembered for (example: `60` seconds)
+    pub ttl: Duration,
+
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl CachingResolver {
+    /// Create a caching resolver in front of `inner`, remembering addresses for `ttl`
+    pub fn new(inner: Arc<dyn Resolver>, ttl: Duration) -> CachingResolver {
+        CachingResolver {
+            inner,
+            ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Forget every address in the cache, so each host is looked up again
+    pub fn clear(&self) {
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+}
+
+impl Resolver for CachingResolver {
+    fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, RequestError> {
+        let key = (normalize(host), port);
+
+        {
+            let mut cache = self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            cache.retain(|_, (since, _)| since.elapsed() < self.ttl);
+            if let Some((_, addrs)) = cache.get(&key) {
+                return Ok(addrs.clone());
+            }
+        }
+
+        // the lock is not held while looking up, so a slow lookup does not hold up other hosts
+        let addrs = self.inner.resolve(host, port)?;
+        if !addrs.is_empty() {
+            let mut cache = self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            cache.insert(key, (Instant::now(), addrs.clone()));
+        }
+
+        Ok(addrs)
+    }
+}
+
+/// A host name as it is looked up: lowercase, and without a trailing dot
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Look up the addresses to connect to for `host` with `resolver`, or the system resolver if there is none
+///
+/// An IP address is used as it is, without asking the resolver, and a host with no addresses is an error.
+pub(crate) fn lookup(resolver: Option<&dyn Resolver>, host: &str, port: u16) -> Result<Vec<SocketAddr>, RequestError> {
+    if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+
+    let addrs = match resolver {
+        Some(resolver) => resolver.resolve(host, port)?,
+        None => SystemResolver.resolve(host, port)?,
+    };
+
+    if addrs.is_empty() {
+        return Err(RequestError::with_kind(ErrorKind::Dns, format!("could not resolve address: {}", host)));
+    }
+
+    Ok(addrs)
+}
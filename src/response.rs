@@ -1,42 +1,130 @@
-use std::collections::HashMap;
-use crate::errors::RequestError;
-use crate::request::Request;
+use std::io::{self, BufReader, Read};
+use std::net::SocketAddr;
+
+use crate::errors::{Error, ErrorKind, RequestError, ResponseError};
+use crate::headers::HeaderMap;
+use crate::json::Json;
+use crate::redirect::Redirect;
+use crate::request::{Request, Timeouts};
 use crate::status_code::StatusCode;
+use crate::transport::Stream;
+use crate::wire;
 use crate::ValidRequest;
 /// Describes the response of an HTTP request
 pub trait ValidResponse {
     /// Resend the request that generated this response
     fn resend(&self) -> Result<Response, RequestError>;
+
+    /// The body of the response, as raw bytes
+    fn bytes(&self) -> &[u8];
+
+    /// The body of the response, decoded as text using the charset from the `Content-Type` header (UTF-8 if none is given)
+    ///
+    /// Bytes that are not valid in the charset are replaced with U+FFFD, so this never fails on binary data.
+    fn text(&self) -> String;
+
+    /// The body of the response, parsed as JSON (which must be UTF-8)
+    fn json(&self) -> Result<Json, ResponseError>;
 }
 
 /// Describes the response of an HTTP request, and contains the response data
 #[derive(Clone)]
 pub struct Response {
-    /// The status code of the response
+    /// The status code of the response, with a code that is not known given as the x00 code of its class (example:
+    /// `299` is `StatusCode::OK`)
     pub status_code: StatusCode,
 
-    /// The raw response, as a string
-    pub raw_response: String,
+    /// The status code of the response, exactly as the server sent it (example: `299`)
+    pub status_code_raw: u16,
+
+    /// The raw response, exactly as it was received
+    pub raw_response: Vec<u8>,
 
     /// The headers of the response
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
 
-    /// The body of the response
-    pub body: String,
+    /// The body of the response, with any chunked transfer coding removed
+    pub body: Vec<u8>,
 
-    /// An exact copy of the request that generated this response
+    /// The trailer fields sent after a chunked body (empty if the body was not chunked)
+    pub trailers: HeaderMap,
+
+    /// An exact copy of the request that generated this response (the last one, if redirects were followed)
     pub request_used: Request,
+
+    /// The redirects that were followed on the way to this response, in order
+    pub redirects: Vec<Redirect>,
+
+    /// The address the response came from: whichever of the server's addresses was connected to (the proxy's, if the
+    /// request was sent through one), or `None` if the connection was not over the network
+    pub remote_addr: Option<SocketAddr>,
 }
 
 impl core::fmt::Debug for Response {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "{}", self.raw_response)
+        write!(f, "{}", String::from_utf8_lossy(&self.raw_response))
     }
 }
 
 impl core::fmt::Display for Response {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "{}", self.raw_response)
+        write!(f, "{}", String::from_utf8_lossy(&self.raw_response))
+    }
+}
+
+impl Response {
+    /// The charset parameter of the `Content-Type` header, if there is one
+    fn charset(&self) -> Option<String> {
+        let content_type = self.headers.get("Content-Type")?;
+
+        content_type.split(';').skip(1).find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            if name.trim().eq_ignore_ascii_case("charset") {
+                Some(value.trim().trim_matches('"').to_ascii_lowercase())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Decode `bytes` from the given charset, falling back to UTF-8 for charsets that are not known
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset {
+        "iso-8859-1" | "iso8859-1" | "latin1" | "l1" | "us-ascii" | "ascii" => {
+            bytes.iter().map(|&byte| byte as char).collect()
+        }
+        "windows-1252" | "cp1252" => bytes.iter().map(|&byte| windows_1252(byte)).collect(),
+        "utf-16le" => decode_utf16(bytes, u16::from_le_bytes),
+        "utf-16be" | "utf-16" => decode_utf16(bytes, u16::from_be_bytes),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|pair| match pair {
+        [high, low] => from_bytes([*high, *low]),
+        // a trailing odd byte cannot be a code unit
+        _ => 0xFFFD,
+    });
+
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Map a windows-1252 byte to its character; it only differs from latin-1 in the 0x80..0xA0 range
+fn windows_1252(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '\u{20AC}', '\u{FFFD}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+        '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{FFFD}', '\u{017D}', '\u{FFFD}',
+        '\u{FFFD}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+        '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{FFFD}', '\u{017E}', '\u{0178}',
+    ];
+
+    match byte {
+        0x80..=0x9F => HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
     }
 }
 
@@ -46,4 +134,158 @@ impl ValidResponse for Response {
         let request = self.request_used.clone();
         request.send()
     }
-}
\ No newline at end of file
+
+    fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    fn text(&self) -> String {
+        match self.charset() {
+            Some(charset) => decode_charset(&self.body, &charset),
+            None => String::from_utf8_lossy(&self.body).into_owned(),
+        }
+    }
+
+    fn json(&self) -> Result<Json, ResponseError> {
+        // a byte order mark is allowed, but not required (RFC 8259, section 8.1)
+        let body = self.body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&self.body);
+
+        let text = std::str::from_utf8(body).map_err(|error| {
+            ResponseError::with_kind(ErrorKind::InvalidJson, "response body is not valid UTF-8".to_string())
+                .with_source(error)
+        })?;
+
+        Ok(Json::parse(text)?)
+    }
+}
+
+/// The parts of a response that redirects and retries are decided on, shared by buffered and streaming responses
+pub(crate) trait ResponseHead {
+    fn status_code(&self) -> &StatusCode;
+    fn status_code_raw(&self) -> u16;
+    fn headers(&self) -> &HeaderMap;
+    fn set_redirects(&mut self, redirects: Vec<Redirect>);
+}
+
+impl ResponseHead for Response {
+    fn status_code(&self) -> &StatusCode {
+        &self.status_code
+    }
+
+    fn status_code_raw(&self) -> u16 {
+        self.status_code_raw
+    }
+
+    fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    fn set_redirects(&mut self, redirects: Vec<Redirect>) {
+        self.redirects = redirects;
+    }
+}
+
+/// A response whose body has not been read yet, returned by `Request::send_streaming`
+///
+/// The body is read from the connection as it is used, so a large download never has to fit in memory. It is not
+/// decompressed, and the server is not asked to compress it.
+///
+/// # Examples
+/// ```rust,no_run
+/// use quickhttp::builder::{Builder, ValidBuilder};
+///
+/// let mut response = Builder::new()
+///     .uri("http://example.com/large.bin".to_string())
+///     .build()
+///     .unwrap()
+///     .send_streaming()
+///     .unwrap();
+///
+/// let mut file = std::fs::File::create("large.bin").unwrap();
+/// std::io::copy(&mut response.body, &mut file).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct StreamingResponse {
+    /// The status code of the response, with a code that is not known given as the x00 code of its class (example:
+    /// `299` is `StatusCode::OK`)
+    pub status_code: StatusCode,
+
+    /// The status code of the response, exactly as the server sent it (example: `299`)
+    pub status_code_raw: u16,
+
+    /// The headers of the response
+    pub headers: HeaderMap,
+
+    /// The body of the response, with any chunked transfer coding removed as it is read
+    pub body: ResponseBody,
+
+    /// An exact copy of the request that generated this response (the last one, if redirects were followed)
+    pub request_used: Request,
+
+    /// The redirects that were followed on the way to this response, in order
+    pub redirects: Vec<Redirect>,
+
+    /// The address the response came from: whichever of the server's addresses was connected to (the proxy's, if the
+    /// request was sent through one), or `None` if the connection was not over the network
+    pub remote_addr: Option<SocketAddr>,
+}
+
+impl ResponseHead for StreamingResponse {
+    fn status_code(&self) -> &StatusCode {
+        &self.status_code
+    }
+
+    fn status_code_raw(&self) -> u16 {
+        self.status_code_raw
+    }
+
+    fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    fn set_redirects(&mut self, redirects: Vec<Redirect>) {
+        self.redirects = redirects;
+    }
+}
+
+/// The body of a [`StreamingResponse`], which reads from the connection up to the end of the body and no further
+///
+/// The request's read timeout and deadline apply to every read that has to wait for the server.
+pub struct ResponseBody {
+    reader: wire::BodyReader<BufReader<Box<dyn Stream>>>,
+    timeouts: Timeouts,
+}
+
+impl core::fmt::Debug for ResponseBody {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("ResponseBody").finish_non_exhaustive()
+    }
+}
+
+impl ResponseBody {
+    pub(crate) fn new(reader: wire::BodyReader<BufReader<Box<dyn Stream>>>, timeouts: Timeouts) -> ResponseBody {
+        ResponseBody { reader, timeouts }
+    }
+
+    /// The trailer fields sent after a chunked body, once the body has been read to the end
+    pub fn trailers(&self) -> HeaderMap {
+        self.reader.trailers()
+    }
+}
+
+impl Read for ResponseBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let stream = self.reader.get_ref();
+
+        if stream.buffer().is_empty() {
+            if self.timeouts.expired() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "request deadline exceeded"));
+            }
+            stream
+                .get_ref()
+                .set_read_timeout(self.timeouts.limit(self.timeouts.read))?;
+        }
+
+        self.reader.read(buf)
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::cookies;
+use crate::errors::{Error, ErrorKind, RequestError, TimeoutKind};
+use crate::headers::HeaderMap;
+use crate::request::{Request, Timeouts};
+use crate::response::ResponseHead;
+
+/// Describes a retry policy, which decides when a failed request is sent again
+pub trait ValidRetryPolicy {
+    /// Create the default policy: up to 3 attempts of idempotent requests that could not connect, or were answered with
+    /// 429, 502, 503 or 504
+    fn new() -> Self;
+
+    /// Set the most times a request is sent, counting the first attempt (example: 5)
+    fn max_attempts(&mut self, max_attempts: u32) -> &mut Self;
+
+    /// Set the delay before the first retry, which doubles for each retry after it (example: 200 milliseconds)
+    fn base_delay(&mut self, base_delay: Duration) -> &mut Self;
+
+    /// Set the longest delay between two attempts (example: 30 seconds)
+    fn max_delay(&mut self, max_delay: Duration) -> &mut Self;
+
+    /// Set whether to randomise each delay, so that many clients do not retry at the same moment (example: true)
+    fn jitter(&mut self, jitter: bool) -> &mut Self;
+
+    /// Set the error kinds that are retried (example: `vec![ErrorKind::Connect, ErrorKind::Io]`)
+    fn error_kinds(&mut self, error_kinds: Vec<ErrorKind>) -> &mut Self;
+
+    /// Set the response status codes that are retried (example: `vec![429, 503]`)
+    fn status_codes(&mut self, status_codes: Vec<u16>) -> &mut Self;
+
+    /// Set the methods that are retried (example: `vec!["GET".to_string(), "POST".to_string()]`)
+    fn methods(&mut self, methods: Vec<String>) -> &mut Self;
+
+    /// Set whether to wait as long as the `Retry-After` header of a response asks, instead of backing off (example: true)
+    fn respect_retry_after(&mut self, respect_retry_after: bool) -> &mut Self;
+}
+
+/// A retry policy, with exponential backoff between attempts
+///
+/// Only idempotent methods are retried by default, since sending anything else twice could repeat its effect.
+///
+/// # Examples
+/// ```rust
+/// use std::time::Duration;
+/// use quickhttp::retry::{RetryPolicy, ValidRetryPolicy};
+/// use quickhttp::builder::{Builder, ValidBuilder};
+///
+/// let mut policy = RetryPolicy::new();
+/// policy.max_attempts(5).base_delay(Duration::from_millis(100));
+///
+/// let request = Builder::new()
+///     .host("example.com".to_string())
+///     .path("/".to_string())
+///     .retry(policy)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The most times a request is sent, counting the first attempt (example: `3`)
+    pub max_attempts: u32,
+
+    /// The delay before the first retry, which doubles for each retry after it (example: `200` milliseconds)
+    pub base_delay: Duration,
+
+    /// The longest delay between two attempts; a `Retry-After` asking for longer is not waited for (example: `30`
+    /// seconds)
+    pub max_delay: Duration,
+
+    /// Whether each delay is picked at random between half of it and all of it
+    pub jitter: bool,
+
+    /// The kinds of error that are retried (example: `ErrorKind::Connect`)
+    pub error_kinds: Vec<ErrorKind>,
+
+    /// The response status codes that are retried (example: `503`)
+    pub status_codes: Vec<u16>,
+
+    /// The methods that are retried (example: `GET`)
+    pub methods: Vec<String>,
+
+    /// Whether a retried response's `Retry-After` header sets the delay, instead of the backoff
+    pub respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::new()
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before sending `method` again, or `None` if `result` should be returned as it is
+    ///
+    /// `attempts` is how many times the request has been sent so far.
+    pub(crate) fn delay(
+        &self,
+        attempts: u32,
+        method: &str,
+        result: &Result<impl ResponseHead, RequestError>,
+    ) -> Option<Duration> {
+        if attempts >= self.max_attempts || !self.methods.iter().any(|allowed| allowed.eq_ignore_ascii_case(method)) {
+            return None;
+        }
+
+        match result {
+            Err(error) if self.error_kinds.contains(&error.kind()) => Some(self.backoff(attempts)),
+            Err(_) => None,
+            Ok(response) => {
+                if !self.status_codes.contains(&response.status_code_raw()) {
+                    return None;
+                }
+
+                match retry_after(response.headers()).filter(|_| self.respect_retry_after) {
+                    Some(delay) if delay > self.max_delay => None,
+                    Some(delay) => Some(delay),
+                    None => Some(self.backoff(attempts)),
+                }
+            }
+        }
+    }
+
+    /// The backoff after the given number of attempts: `base_delay`, doubled for each attempt after the first
+    fn backoff(&self, attempts: u32) -> Duration {
+        let doublings = attempts.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .checked_mul(1 << doublings)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        if !self.jitter {
+            return delay;
+        }
+
+        // "equal jitter": keep half of the delay, and pick the rest at random
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(attempts);
+        let fraction = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+
+        delay / 2 + (delay / 2).mul_f64(fraction)
+    }
+}
+
+/// How long the `Retry-After` header asks to wait, given as a number of seconds or as an HTTP-date
+/// (RFC 9110, section 10.2.3)
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get("Retry-After")?.trim();
+
+    if !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Some(Duration::from_secs(value.parse().unwrap_or(u64::MAX)));
+    }
+
+    let date = cookies::parse_date(value)?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO))
+}
+
+impl ValidRetryPolicy for RetryPolicy {
+    fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            error_kinds: vec![ErrorKind::Connect, ErrorKind::Timeout(TimeoutKind::Connect)],
+            status_codes: vec![429, 502, 503, 504],
+            methods: ["GET", "HEAD", "PUT", "DELETE", "OPTIONS", "TRACE"]
+                .iter()
+                .map(|method| method.to_string())
+                .collect(),
+            respect_retry_after: true,
+        }
+    }
+
+    fn max_attempts(&mut self, max_attempts: u32) -> &mut Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    fn base_delay(&mut self, base_delay: Duration) -> &mut Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    fn max_delay(&mut self, max_delay: Duration) -> &mut Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    fn jitter(&mut self, jitter: bool) -> &mut Self {
+        self.jitter = jitter;
+        self
+    }
+
+    fn error_kinds(&mut self, error_kinds: Vec<ErrorKind>) -> &mut Self {
+        self.error_kinds = error_kinds;
+        self
+    }
+
+    fn status_codes(&mut self, status_codes: Vec<u16>) -> &mut Self {
+        self.status_codes = status_codes;
+        self
+    }
+
+    fn methods(&mut self, methods: Vec<String>) -> &mut Self {
+        self.methods = methods;
+        self
+    }
+
+    fn respect_retry_after(&mut self, respect_retry_after: bool) -> &mut Self {
+        self.respect_retry_after = respect_retry_after;
+        self
+    }
+}
+
+impl Timeouts {
+    /// Whether there is time to wait for `delay` before the deadline
+    pub(crate) fn allows(&self, delay: Duration) -> bool {
+        self.deadline.is_none_or(|deadline| Instant::now() + delay < deadline)
+    }
+}
+
+impl Request {
+    /// Send the request with `send`, sending it again whenever `policy` says the result should be retried
+    pub(crate) fn send_retrying<R: ResponseHead>(
+        &self,
+        policy: Option<&RetryPolicy>,
+        timeouts: &Timeouts,
+        mut send: impl FnMut(&Request) -> Result<R, RequestError>,
+    ) -> Result<R, RequestError> {
+        let mut attempts = 1;
+
+        loop {
+            let result = send(self);
+
+            let delay = match policy.and_then(|policy| policy.delay(attempts, &self.method, &result)) {
+                Some(delay) if timeouts.allows(delay) => delay,
+                _ => return result,
+            };
+
+            // a body read from a source cannot be sent again, so the result of the last attempt is the one to give back
+            if self.body.is_spent() {
+                return result;
+            }
+
+            std::thread::sleep(delay);
+            attempts += 1;
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::io::{self, Read, Write};
+use std::net::IpAddr;
+
+use crate::errors::{Error, ErrorKind, RequestError, TimeoutKind};
+use crate::proxy;
+use crate::resolver::{self, Resolver};
+use crate::transport::Stream;
+use crate::url::Url;
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0x00;
+const USERNAME_PASSWORD: u8 = 0x02;
+const NO_ACCEPTABLE_METHODS: u8 = 0xFF;
+const CONNECT: u8 = 0x01;
+const IPV4: u8 = 0x01;
+const DOMAIN_NAME: u8 = 0x03;
+const IPV6: u8 = 0x04;
+
+fn fail(message: String) -> RequestError {
+    RequestError::with_kind(ErrorKind::Connect, message)
+}
+
+/// Ask the SOCKS5 proxy on the other end of `stream` to connect to `host` and `port`, authenticating with the
+/// username and password from its URL if it has them (RFC 1928, RFC 1929)
+///
+/// A `socks5h` proxy is sent the host name to resolve itself, while for a `socks5` proxy it is resolved locally, with
+/// `resolver` if there is one.
+pub(crate) fn connect(
+    stream: &mut Box<dyn Stream>,
+    host: &str,
+    port: u16,
+    server: &Url,
+    resolver: Option<&dyn Resolver>,
+) -> Result<(), RequestError> {
+    let address = address(host, port, server.scheme() == "socks5h", resolver)?;
+
+    let io_failed = |error: io::Error| {
+        let message = match error.kind() {
+            io::ErrorKind::UnexpectedEof => "SOCKS proxy closed the connection",
+            _ => "SOCKS handshake failed",
+        };
+        RequestError::io(ErrorKind::Connect, TimeoutKind::Connect, message, error)
+    };
+
+    let credentials = proxy::credentials(server);
+    let method = greet(stream, credentials.is_some()).map_err(io_failed)?;
+
+    match (method, credentials) {
+        (NO_AUTHENTICATION, _) => {}
+        (USERNAME_PASSWORD, Some((username, password))) => {
+            if !authenticate(stream, &username, &password).map_err(io_failed)? {
+                return Err(fail("SOCKS proxy rejected the username and password".to_string()));
+            }
+        }
+        (NO_ACCEPTABLE_METHODS, _) => {
+            return Err(fail("SOCKS proxy accepts none of the offered authentication methods".to_string()))
+        }
+        (method, _) => return Err(fail(format!("SOCKS proxy chose an unknown authentication method: {}", method))),
+    }
+
+    let reply = request(stream, &address, port).map_err(io_failed)?;
+    if reply != 0 {
+        return Err(fail(format!("SOCKS proxy could not connect to {}: {}", host, reply_message(reply))));
+    }
+
+    Ok(())
+}
+
+/// The address to ask the proxy to connect to, with its type: an IP address as it is, and a host name either as it
+/// is, for the proxy to resolve, or resolved here
+fn address(host: &str, port: u16, remote_dns: bool, resolver: Option<&dyn Resolver>) -> Result<Vec<u8>, RequestError> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let ip = match host.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) if remote_dns => {
+            if host.len() > 255 {
+                return Err(fail(format!("host name is too long for SOCKS: {}", host)));
+            }
+
+            let mut address = vec![DOMAIN_NAME, host.len() as u8];
+            address.extend_from_slice(host.as_bytes());
+            return Ok(address);
+        }
+        // the proxy only takes one address, so the first is used
+        Err(_) => resolver::lookup(resolver, host, port)?[0].ip(),
+    };
+
+    Ok(match ip {
+        IpAddr::V4(ip) => [&[IPV4][..], &ip.octets()].concat(),
+        IpAddr::V6(ip) => [&[IPV6][..], &ip.octets()].concat(),
+    })
+}
+
+/// Offer the authentication methods, returning the one the proxy chose
+fn greet<S: Read + Write + ?Sized>(stream: &mut S, with_password: bool) -> io::Result<u8> {
+    if with_password {
+        stream.write_all(&[VERSION, 2, NO_AUTHENTICATION, USERNAME_PASSWORD])?;
+    } else {
+        stream.write_all(&[VERSION, 1, NO_AUTHENTICATION])?;
+    }
+    stream.flush()?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a SOCKS5 proxy"));
+    }
+
+    Ok(reply[1])
+}
+
+/// Send the username and password, returning whether the proxy accepted them (RFC 1929)
+fn authenticate<S: Read + Write + ?Sized>(stream: &mut S, username: &str, password: &str) -> io::Result<bool> {
+    if username.len() > 255 || password.len() > 255 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "SOCKS username or password is too long"));
+    }
+
+    let mut message = vec![1, username.len() as u8];
+    message.extend_from_slice(username.as_bytes());
+    message.push(password.len() as u8);
+    message.extend_from_slice(password.as_bytes());
+    stream.write_all(&message)?;
+    stream.flush()?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply)?;
+
+    Ok(reply[1] == 0)
+}
+
+/// Send the `CONNECT` request, returning the reply code (`0` for success) once the whole reply has been read
+fn request<S: Read + Write + ?Sized>(stream: &mut S, address: &[u8], port: u16) -> io::Result<u8> {
+    let mut message = vec![VERSION, CONNECT, 0];
+    message.extend_from_slice(address);
+    message.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&message)?;
+    stream.flush()?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a SOCKS5 proxy"));
+    }
+
+    // the rest of the reply is the address the proxy connected from, which is not needed
+    let length = match reply[3] {
+        IPV4 => 4,
+        IPV6 => 16,
+        DOMAIN_NAME => {
+            let mut length = [0u8; 1];
+            stream.read_exact(&mut length)?;
+            usize::from(length[0])
+        }
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid address type in SOCKS reply")),
+    };
+    stream.read_exact(&mut vec![0; length + 2])?;
+
+    Ok(reply[1])
+}
+
+/// Describe a reply code (RFC 1928, section 6)
+fn reply_message(reply: u8) -> String {
+    match reply {
+        0x01 => "general SOCKS server failure".to_string(),
+        0x02 => "connection not allowed by ruleset".to_string(),
+        0x03 => "network unreachable".to_string(),
+        0x04 => "host unreachable".to_string(),
+        0x05 => "connection refused".to_string(),
+        0x06 => "TTL expired".to_string(),
+        0x07 => "command not supported".to_string(),
+        0x08 => "address type not supported".to_string(),
+        reply => format!("unknown reply {}", reply),
+    }
+}
@@ -0,0 +1,449 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use crate::errors::{ErrorKind, RequestError, TimeoutKind};
+use crate::resolver::{self, Resolver};
+
+/// Describes a connection that a request can be written to and a response read from
+pub trait Stream: Read + Write + Send {
+    /// Whether the connection is still open with nothing waiting to be read, so it can be reused for another request
+    ///
+    /// This is checked before a client takes a connection out of its pool. Streams that cannot tell are assumed to be idle.
+    fn is_idle(&self) -> bool {
+        true
+    }
+
+    /// Set how long a read may block before it fails with a timeout (`None` blocks forever)
+    ///
+    /// Streams that do not support timeouts ignore this.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        let _ = timeout;
+        Ok(())
+    }
+
+    /// Set how long a write may block before it fails with a timeout (`None` blocks forever)
+    ///
+    /// Streams that do not support timeouts ignore this.
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        let _ = timeout;
+        Ok(())
+    }
+
+    /// The address of the other end of the connection, if it is a network connection
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+impl<S: Stream + ?Sized> Stream for Box<S> {
+    fn is_idle(&self) -> bool {
+        (**self).is_idle()
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_write_timeout(timeout)
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        (**self).peer_addr()
+    }
+}
+
+impl Stream for TcpStream {
+    fn is_idle(&self) -> bool {
+        if self.set_nonblocking(true).is_err() {
+            return false;
+        }
+
+        // a closed socket reads as end of file straight away, while a live one has nothing to read yet
+        let idle = matches!(
+            self.peek(&mut [0u8; 1]),
+            Err(ref error) if error.kind() == std::io::ErrorKind::WouldBlock
+        );
+
+        self.set_nonblocking(false).is_ok() && idle
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+
+    fn peer_addr(&self) -> Option<SocketAddr> {
+        TcpStream::peer_addr(self).ok()
+    }
+}
+
+/// Describes a way of opening the connection that a request is sent over (for example, a plain TCP connection)
+pub trait Connector: Send + Sync {
+    /// Open a connection to the given host and port
+    fn connect(&self, host: &str, port: u16) -> Result<Box<dyn Stream>, RequestError>;
+}
+
+/// Describes a way of securing a connection with TLS, for `https://` requests
+pub trait TlsConnector: Send + Sync {
+    /// Perform the TLS handshake over an open connection, verifying the server as `host`
+    fn connect(&self, host: &str, stream: Box<dyn Stream>) -> Result<Box<dyn Stream>, RequestError>;
+}
+
+impl core::fmt::Debug for dyn Connector {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Connector")
+    }
+}
+
+impl core::fmt::Debug for dyn TlsConnector {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "TlsConnector")
+    }
+}
+
+/// How long to wait for a connection attempt before starting the next one alongside it (RFC 8305, section 5)
+pub(crate) const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Order the addresses a host resolved to for connecting, alternating between IPv6 and IPv4 but starting with the
+/// family of the address the resolver put first (RFC 8305, section 4)
+pub(crate) fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_is_ipv6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (mut preferred, mut other): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    preferred.reverse();
+    other.reverse();
+
+    let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+    while let Some(addr) = preferred.pop() {
+        ordered.push(addr);
+        ordered.extend(other.pop());
+    }
+    ordered.extend(other.into_iter().rev());
+    ordered
+}
+
+/// The default connector, which opens a plain TCP connection
+///
+/// Every address the host resolves to is tried, racing IPv6 and IPv4 with Happy Eyeballs (RFC 8305): each attempt is
+/// given a head start of 250 milliseconds before the next one is started alongside it, and the first to connect is used.
+#[derive(Clone, Debug)]
+pub struct TcpConnector {
+    /// How long to wait for the connection to be established, across all of the attempts (example: `5` seconds)
+    pub connect_timeout: Duration,
+
+    /// The resolver that host names are looked up with (the system resolver if not set)
+    pub resolver: Option<Arc<dyn Resolver>>,
+}
+
+impl TcpConnector {
+    /// Create a TCP connector with a 5 second connect timeout, which uses the system resolver
+    pub fn new() -> TcpConnector {
+        TcpConnector {
+            connect_timeout: Duration::from_secs(5),
+            resolver: None,
+        }
+    }
+}
+
+impl Default for TcpConnector {
+    fn default() -> TcpConnector {
+        TcpConnector::new()
+    }
+}
+
+impl TcpConnector {
+    /// Connect to whichever of the addresses accepts a connection first, starting a new attempt whenever the last one
+    /// fails or has not connected within the attempt delay
+    pub(crate) fn race(&self, addrs: Vec<SocketAddr>) -> Result<TcpStream, RequestError> {
+        let deadline = Instant::now() + self.connect_timeout;
+        let (sender, receiver) = mpsc::channel();
+
+        let mut waiting = addrs.into_iter();
+        let mut started = Vec::new();
+        let mut failures = Vec::new();
+
+        // each time round, the last attempt has either failed or had its head start, so the next one is started
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if !remaining.is_zero() {
+                if let Some(addr) = waiting.next() {
+                    let sender = sender.clone();
+                    std::thread::spawn(move || {
+                        // an attempt that connects after another has won is closed when its result is dropped
+                        let _ = sender.send((addr, TcpStream::connect_timeout(&addr, remaining)));
+                    });
+                    started.push(addr);
+                }
+            }
+
+            if failures.len() == started.len() || remaining.is_zero() {
+                break;
+            }
+
+            let wait = if waiting.len() > 0 {
+                remaining.min(ATTEMPT_DELAY)
+            } else {
+                remaining
+            };
+
+            match receiver.recv_timeout(wait) {
+                Ok((_, Ok(stream))) => return Ok(stream),
+                Ok((addr, Err(error))) => failures.push((addr, error)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        // attempts that were still going when time ran out failed by timing out
+        for &addr in &started {
+            if !failures.iter().any(|(failed, _)| *failed == addr) {
+                failures.push((addr, io::Error::new(io::ErrorKind::TimedOut, "connection timed out")));
+            }
+        }
+
+        // the failures came in as the attempts finished, but are reported in the order they were started
+        failures.sort_by_key(|(addr, _)| started.iter().position(|started| started == addr));
+        Err(RequestError::attempts(failures))
+    }
+}
+
+impl Connector for TcpConnector {
+    fn connect(&self, host: &str, port: u16) -> Result<Box<dyn Stream>, RequestError> {
+        let addrs = interleave(resolver::lookup(self.resolver.as_deref(), host, port)?);
+
+        match addrs.as_slice() {
+            // a single address needs no racing, but its failure is reported in the same way
+            [addr] => match TcpStream::connect_timeout(addr, self.connect_timeout) {
+                Ok(stream) => Ok(Box::new(stream)),
+                Err(error) => Err(RequestError::attempts(vec![(*addr, error)])),
+            },
+            _ => Ok(Box::new(self.race(addrs)?)),
+        }
+    }
+}
+
+/// A connector that opens a connection to a Unix domain socket, wherever the request is addressed to (for example, to
+/// talk to the Docker daemon at `/var/run/docker.sock`)
+///
+/// The host and port of the request are still sent in its `Host` header, so they should be whatever the server expects
+/// (usually `localhost`).
+///
+/// # Examples
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use quickhttp::builder::{Builder, ValidBuilder};
+/// use quickhttp::transport::UnixConnector;
+///
+/// let request = Builder::new()
+///     .uri("http://localhost/v1.43/containers/json".to_string())
+///     .connector(Arc::new(UnixConnector::new("/var/run/docker.sock")))
+///     .build()
+///     .unwrap();
+/// ```
+#[cfg(unix)]
+#[derive(Clone, Debug)]
+pub struct UnixConnector {
+    /// The path of the socket (example: `/var/run/docker.sock`)
+    pub path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixConnector {
+    /// Create a connector for the socket at `path`
+    pub fn new<P: Into<std::path::PathBuf>>(path: P) -> UnixConnector {
+        UnixConnector { path: path.into() }
+    }
+}
+
+#[cfg(unix)]
+impl Connector for UnixConnector {
+    fn connect(&self, _host: &str, _port: u16) -> Result<Box<dyn Stream>, RequestError> {
+        match UnixStream::connect(&self.path) {
+            Ok(stream) => Ok(Box::new(stream)),
+            Err(error) => Err(RequestError::io(
+                ErrorKind::Connect,
+                TimeoutKind::Connect,
+                &format!("could not connect to socket {}", self.path.display()),
+                error,
+            )),
+        }
+    }
+}
+
+/// A Unix domain socket cannot be peeked at without consuming data on stable Rust, so one is assumed to be idle
+#[cfg(unix)]
+impl Stream for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_write_timeout(self, timeout)
+    }
+}
+
+/// TLS support using `rustls`, available with the `tls` feature
+#[cfg(feature = "tls")]
+mod rustls_connector {
+    use std::io::{self, Read, Write};
+    use std::net::SocketAddr;
+    use std::sync::{Arc, OnceLock};
+    use std::time::Duration;
+
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+    use super::{Stream, TlsConnector};
+    use crate::errors::{Error, ErrorKind, RequestError, TimeoutKind};
+
+    /// A TLS connector built on `rustls`, which verifies servers against the Mozilla root certificates by default
+    #[derive(Clone, Debug)]
+    pub struct RustlsConnector {
+        config: Arc<ClientConfig>,
+    }
+
+    impl RustlsConnector {
+        /// Create a connector that trusts the Mozilla root certificates (from `webpki-roots`)
+        pub fn new() -> RustlsConnector {
+            static DEFAULT: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+            let config = DEFAULT.get_or_init(|| {
+                let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+                Arc::new(config_with_roots(roots))
+            });
+
+            RustlsConnector {
+                config: config.clone(),
+            }
+        }
+
+        /// Create a connector that uses a custom `rustls` configuration (for example, to trust a private CA)
+        pub fn with_config(config: Arc<ClientConfig>) -> RustlsConnector {
+            RustlsConnector { config }
+        }
+    }
+
+    impl Default for RustlsConnector {
+        fn default() -> RustlsConnector {
+            RustlsConnector::new()
+        }
+    }
+
+    /// Build a client configuration that trusts the given roots, using the `ring` crypto provider
+    pub fn config_with_roots(roots: RootCertStore) -> ClientConfig {
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("the ring provider supports the default protocol versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth()
+    }
+
+    /// A connection secured with `rustls`
+    struct TlsStream(StreamOwned<ClientConnection, Box<dyn Stream>>);
+
+    impl Read for TlsStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buf) {
+                // many servers close the connection without sending close_notify; the body framing catches truncation
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+                result => result,
+            }
+        }
+    }
+
+    impl Write for TlsStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    impl Stream for TlsStream {
+        fn is_idle(&self) -> bool {
+            self.0.sock.is_idle()
+        }
+
+        fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+            self.0.sock.set_read_timeout(timeout)
+        }
+
+        fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+            self.0.sock.set_write_timeout(timeout)
+        }
+
+        fn peer_addr(&self) -> Option<SocketAddr> {
+            self.0.sock.peer_addr()
+        }
+    }
+
+    impl TlsConnector for RustlsConnector {
+        fn connect(&self, host: &str, stream: Box<dyn Stream>) -> Result<Box<dyn Stream>, RequestError> {
+            // IPv6 literals are written in brackets in URIs, but not in server names
+            let name = host.trim_start_matches('[').trim_end_matches(']').to_string();
+
+            let server_name = match ServerName::try_from(name) {
+                Ok(server_name) => server_name,
+                Err(_) => {
+                    return Err(RequestError::with_kind(
+                        ErrorKind::Tls,
+                        format!("invalid TLS server name: {}", host),
+                    ))
+                }
+            };
+
+            let connection = match ClientConnection::new(self.config.clone(), server_name) {
+                Ok(connection) => connection,
+                Err(error) => {
+                    return Err(RequestError::with_kind(ErrorKind::Tls, format!("could not start TLS: {}", error))
+                        .with_source(error))
+                }
+            };
+
+            let mut stream = StreamOwned::new(connection, stream);
+
+            // finish the handshake now, so that certificate errors are reported as connection errors
+            while stream.conn.is_handshaking() {
+                if let Err(error) = stream.conn.complete_io(&mut stream.sock) {
+                    return Err(RequestError::io(ErrorKind::Tls, TimeoutKind::Connect, "TLS handshake failed", error));
+                }
+            }
+
+            Ok(Box::new(TlsStream(stream)))
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+pub use rustls_connector::{config_with_roots, RustlsConnector};
+
+/// The TLS connector used for `https://` requests that do not set one
+pub(crate) fn default_tls_connector() -> Result<Box<dyn TlsConnector>, RequestError> {
+    #[cfg(feature = "tls")]
+    {
+        Ok(Box::new(RustlsConnector::new()))
+    }
+
+    #[cfg(not(feature = "tls"))]
+    {
+        use crate::errors::Error;
+
+        Err(RequestError::with_kind(
+            ErrorKind::Tls,
+            "https requires the `tls` feature, or a TLS connector set on the request".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,587 @@
+use crate::errors::{BuilderError, Error, ErrorKind};
+
+/// A URL, parsed according to RFC 3986
+///
+/// Parsing normalises the URL: the scheme and host are lowercased, dot segments are removed from the path, and any
+/// characters that are not allowed in a component are percent-encoded.
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::Url;
+///
+/// let url = Url::parse("http://user@[::1]:8080/a/./b/../c?q=1#top").unwrap();
+/// assert_eq!(url.host(), Some("::1"));
+/// assert_eq!(url.port(), Some(8080));
+/// assert_eq!(url.path(), "/a/c");
+/// assert_eq!(url.query(), Some("q=1"));
+///
+/// let next = url.join("../d?page=2").unwrap();
+/// assert_eq!(next.to_string(), "http://user@[::1]:8080/d?page=2");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Url {
+    scheme: String,
+    username: String,
+    password: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+/// The components of a URI reference, split as in RFC 3986, appendix B
+struct Parts<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+impl<'a> Parts<'a> {
+    fn split(input: &'a str) -> Parts<'a> {
+        let (rest, fragment) = match input.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (input, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+
+        // a scheme is only present if the colon comes before any slash
+        let (scheme, rest) = match rest.find(':') {
+            Some(colon) if !rest[..colon].contains('/') && is_scheme(&rest[..colon]) => {
+                (Some(&rest[..colon]), &rest[colon + 1..])
+            }
+            _ => (None, rest),
+        };
+
+        let (authority, path) = match rest.strip_prefix("//") {
+            Some(rest) => match rest.find('/') {
+                Some(slash) => (Some(&rest[..slash]), &rest[slash..]),
+                None => (Some(rest), ""),
+            },
+            None => (None, rest),
+        };
+
+        Parts {
+            scheme,
+            authority,
+            path,
+            query,
+            fragment,
+        }
+    }
+}
+
+fn is_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+}
+
+fn fail(message: String) -> BuilderError {
+    BuilderError::with_kind(ErrorKind::InvalidUrl, format!("invalid URL: {}", message))
+}
+
+impl Url {
+    /// Parse an absolute URL (example: `https://example.com:8443/path?query#fragment`)
+    pub fn parse(input: &str) -> Result<Url, BuilderError> {
+        let input = input.trim();
+        if input.chars().any(|c| c.is_ascii_control()) {
+            return Err(fail(format!("control characters are not allowed: {:?}", input)));
+        }
+
+        let parts = Parts::split(input);
+
+        let scheme = match parts.scheme {
+            Some(scheme) => scheme.to_ascii_lowercase(),
+            None => return Err(fail(format!("missing scheme: {}", input))),
+        };
+
+        let mut url = Url {
+            scheme,
+            username: String::new(),
+            password: None,
+            host: None,
+            port: None,
+            path: String::new(),
+            query: parts.query.map(|query| encode(query, is_query_char)),
+            fragment: parts.fragment.map(|fragment| encode(fragment, is_query_char)),
+        };
+
+        if let Some(authority) = parts.authority {
+            url.parse_authority(authority)?;
+        }
+
+        let path = encode(parts.path, is_path_char);
+        url.path = if url.host.is_some() {
+            if path.is_empty() {
+                "/".to_string()
+            } else {
+                remove_dot_segments(&path)
+            }
+        } else {
+            path
+        };
+
+        Ok(url)
+    }
+
+    fn parse_authority(&mut self, authority: &str) -> Result<(), BuilderError> {
+        let host_port = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => {
+                match userinfo.split_once(':') {
+                    Some((username, password)) => {
+                        self.username = encode(username, is_userinfo_char);
+                        self.password = Some(encode(password, is_userinfo_char));
+                    }
+                    None => self.username = encode(userinfo, is_userinfo_char),
+                }
+                host_port
+            }
+            None => authority,
+        };
+
+        let (host, port) = if let Some(rest) = host_port.strip_prefix('[') {
+            // an IP literal, such as [::1]:8080
+            let (literal, rest) = match rest.split_once(']') {
+                Some(parts) => parts,
+                None => return Err(fail(format!("unclosed IPv6 literal: {}", host_port))),
+            };
+            if literal.parse::<std::net::Ipv6Addr>().is_err() {
+                return Err(fail(format!("invalid IPv6 address: {}", literal)));
+            }
+
+            let port = match rest {
+                "" => None,
+                _ => match rest.strip_prefix(':') {
+                    Some(port) => Some(port),
+                    None => return Err(fail(format!("unexpected characters after IPv6 literal: {}", rest))),
+                },
+            };
+            (literal.to_ascii_lowercase(), port)
+        } else {
+            let (host, port) = match host_port.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            };
+            if !host.bytes().all(|byte| is_unreserved(byte) || is_sub_delim(byte) || byte == b'%') {
+                return Err(fail(format!("invalid host: {}", host)));
+            }
+
+            // the host ends up in the Host header, so once decoded it must still be a plain name or IPv4 address,
+            // without control characters or anything else that could break out of the header
+            let decoded = percent_decode(host);
+            if !decoded.bytes().all(|byte| is_unreserved(byte) || is_sub_delim(byte)) {
+                return Err(fail(format!("invalid host: {}", host)));
+            }
+            (decoded.to_ascii_lowercase(), port)
+        };
+
+        self.port = match port {
+            None | Some("") => None,
+            Some(port) => {
+                if !port.bytes().all(|byte| byte.is_ascii_digit()) {
+                    return Err(fail(format!("invalid port: {}", port)));
+                }
+                match port.parse::<u16>() {
+                    Ok(port) => Some(port),
+                    Err(_) => return Err(fail(format!("port is out of range: {}", port))),
+                }
+            }
+        };
+        self.host = Some(host);
+
+        Ok(())
+    }
+
+    /// Resolve a URI reference (such as `../other?x=1` or `//cdn.example.com/a`) against this URL, as in RFC 3986, section 5
+    pub fn join(&self, reference: &str) -> Result<Url, BuilderError> {
+        let reference = reference.trim();
+        let parts = Parts::split(reference);
+
+        if parts.scheme.is_some() {
+            return Url::parse(reference);
+        }
+
+        let mut target = format!("{}:", self.scheme);
+
+        if let Some(authority) = parts.authority {
+            target.push_str("//");
+            target.push_str(authority);
+            target.push_str(parts.path);
+            push_component(&mut target, '?', parts.query);
+        } else {
+            target.push_str(&self.authority());
+
+            if parts.path.is_empty() {
+                target.push_str(&self.path);
+                push_component(&mut target, '?', parts.query.or(self.query.as_deref()));
+            } else {
+                if parts.path.starts_with('/') {
+                    target.push_str(parts.path);
+                } else {
+                    // merge the reference with everything up to the last segment of the base path
+                    let base = match self.path.rfind('/') {
+                        Some(slash) => &self.path[..=slash],
+                        None if self.host.is_some() => "/",
+                        None => "",
+                    };
+                    target.push_str(base);
+                    target.push_str(parts.path);
+                }
+                push_component(&mut target, '?', parts.query);
+            }
+        }
+
+        push_component(&mut target, '#', parts.fragment);
+
+        let mut url = Url::parse(&target)?;
+        url.path = remove_dot_segments(&url.path);
+        Ok(url)
+    }
+
+    /// The scheme, in lowercase (example: `https`)
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// The username from the userinfo, still percent-encoded (empty if there is none)
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// The password from the userinfo, still percent-encoded
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    /// The host, without the brackets around an IPv6 address (example: `example.com` or `::1`)
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    /// The port, if one is given explicitly
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// The port, or the default port for the scheme if none is given (80 for `http`, 443 for `https`)
+    pub fn port_or_default(&self) -> Option<u16> {
+        self.port.or(match self.scheme.as_str() {
+            "http" | "ws" => Some(80),
+            "https" | "wss" => Some(443),
+            _ => None,
+        })
+    }
+
+    /// The path, percent-encoded (example: `/search`)
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The query, percent-encoded and without the leading `?`
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// The name and value pairs of the query, decoded (example: `("q", "rust lang")` for `?q=rust+lang`)
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        parse_query(self.query().unwrap_or(""))
+    }
+
+    /// The fragment, percent-encoded and without the leading `#`
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.as_deref()
+    }
+
+    /// The path and query, as sent in the request line (example: `/search?q=rust`)
+    pub fn request_target(&self) -> String {
+        let mut target = self.path.clone();
+        push_component(&mut target, '?', self.query.as_deref());
+        target
+    }
+
+    /// The `//userinfo@host:port` part of the URL, or an empty string if there is no host
+    fn authority(&self) -> String {
+        let host = match &self.host {
+            Some(host) => host,
+            None => return String::new(),
+        };
+
+        let mut authority = "//".to_string();
+
+        if !self.username.is_empty() || self.password.is_some() {
+            authority.push_str(&self.username);
+            push_component(&mut authority, ':', self.password.as_deref());
+            authority.push('@');
+        }
+
+        if host.contains(':') {
+            authority.push_str(&format!("[{}]", host));
+        } else {
+            authority.push_str(&encode(host, |byte| is_unreserved(byte) || is_sub_delim(byte)));
+        }
+
+        if let Some(port) = self.port {
+            authority.push_str(&format!(":{}", port));
+        }
+
+        authority
+    }
+}
+
+impl core::fmt::Display for Url {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}:{}{}", self.scheme, self.authority(), self.request_target())?;
+
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{}", fragment)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Url {
+    type Err = BuilderError;
+
+    fn from_str(input: &str) -> Result<Url, BuilderError> {
+        Url::parse(input)
+    }
+}
+
+fn push_component(target: &mut String, separator: char, component: Option<&str>) {
+    if let Some(component) = component {
+        target.push(separator);
+        target.push_str(component);
+    }
+}
+
+/// Remove `.` and `..` segments from a path (RFC 3986, section 5.2.4)
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output: Vec<&str> = Vec::new();
+
+    while !input.is_empty() {
+        if let Some(rest) = input.strip_prefix("../").or_else(|| input.strip_prefix("./")) {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") || input == "/.." {
+            input = if input == "/.." { "/" } else { &input[3..] };
+            output.pop();
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            // move the first segment, with its leading slash, to the output
+            let end = match input[1..].find('/') {
+                Some(slash) => slash + 1,
+                None => input.len(),
+            };
+            output.push(&input[..end]);
+            input = &input[end..];
+        }
+    }
+
+    output.concat()
+}
+
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+fn is_sub_delim(byte: u8) -> bool {
+    matches!(byte, b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=')
+}
+
+fn is_userinfo_char(byte: u8) -> bool {
+    is_unreserved(byte) || is_sub_delim(byte)
+}
+
+fn is_path_char(byte: u8) -> bool {
+    is_unreserved(byte) || is_sub_delim(byte) || matches!(byte, b':' | b'@' | b'/')
+}
+
+fn is_query_char(byte: u8) -> bool {
+    is_path_char(byte) || byte == b'?'
+}
+
+/// Percent-encode every byte of `input` that is not allowed, leaving existing `%XX` escapes alone
+fn encode(input: &str, allowed: fn(u8) -> bool) -> String {
+    let bytes = input.as_bytes();
+    let mut encoded = String::with_capacity(input.len());
+
+    for (i, &byte) in bytes.iter().enumerate() {
+        let is_escape = byte == b'%'
+            && bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit)
+            && bytes.get(i + 2).is_some_and(u8::is_ascii_hexdigit);
+
+        if allowed(byte) || is_escape {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    encoded
+}
+
+/// Percent-encode a string so it can be used as a single URL component, leaving only unreserved characters as they are
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::url::percent_encode;
+///
+/// assert_eq!(percent_encode("a b/c"), "a%20b%2Fc");
+/// ```
+pub fn percent_encode(input: &str) -> String {
+    input
+        .bytes()
+        .map(|byte| {
+            if is_unreserved(byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{:02X}", byte)
+            }
+        })
+        .collect()
+}
+
+/// Decode the `%XX` escapes in a string; anything that does not decode to UTF-8 is replaced with U+FFFD
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::url::percent_decode;
+///
+/// assert_eq!(percent_decode("caf%C3%A9%20au%20lait"), "café au lait");
+/// ```
+pub fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escape = match bytes.get(i..i + 3) {
+            Some([b'%', high, low]) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                Some(hex_value(*high) << 4 | hex_value(*low))
+            }
+            _ => None,
+        };
+
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Encode a string as a name or value in `application/x-www-form-urlencoded` form, where spaces become `+`
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::url::form_urlencode;
+///
+/// assert_eq!(form_urlencode("a b&c=d"), "a+b%26c%3Dd");
+/// ```
+pub fn form_urlencode(input: &str) -> String {
+    input
+        .bytes()
+        .map(|byte| match byte {
+            b' ' => "+".to_string(),
+            b'*' | b'-' | b'.' | b'_' => (byte as char).to_string(),
+            _ if byte.is_ascii_alphanumeric() => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Encode name and value pairs as a query string or form body, without a leading `?`
+///
+/// Query strings are percent-encoded as URL components, and form bodies as `application/x-www-form-urlencoded`.
+fn encode_pairs(pairs: &[(String, String)], encode: fn(&str) -> String) -> String {
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}={}", encode(name), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Encode name and value pairs as a query string, without the leading `?`
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::url::encode_query;
+///
+/// let pairs = vec![("q".to_string(), "rust & http".to_string()), ("page".to_string(), "2".to_string())];
+/// assert_eq!(encode_query(&pairs), "q=rust%20%26%20http&page=2");
+/// ```
+pub fn encode_query(pairs: &[(String, String)]) -> String {
+    encode_pairs(pairs, percent_encode)
+}
+
+/// Encode name and value pairs as an `application/x-www-form-urlencoded` body
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::url::encode_form;
+///
+/// let pairs = vec![("name".to_string(), "John Smith".to_string()), ("likes".to_string(), "tea+cake".to_string())];
+/// assert_eq!(encode_form(&pairs), "name=John+Smith&likes=tea%2Bcake");
+/// ```
+pub fn encode_form(pairs: &[(String, String)]) -> String {
+    encode_pairs(pairs, form_urlencode)
+}
+
+/// Parse a query string or `application/x-www-form-urlencoded` body into decoded name and value pairs
+///
+/// A leading `?` is ignored, `+` is read as a space, and a name without `=` has an empty value.
+///
+/// # Examples
+/// ```rust
+/// use quickhttp::url::parse_query;
+///
+/// assert_eq!(
+///     parse_query("?q=caf%C3%A9+au+lait&flag&&x=1"),
+///     vec![
+///         ("q".to_string(), "café au lait".to_string()),
+///         ("flag".to_string(), "".to_string()),
+///         ("x".to_string(), "1".to_string()),
+///     ]
+/// );
+/// ```
+pub fn parse_query(query: &str) -> Vec<(String, String)> {
+    let decode = |component: &str| percent_decode(&component.replace('+', " "));
+
+    query
+        .strip_prefix('?')
+        .unwrap_or(query)
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((name, value)) => (decode(name), decode(value)),
+            None => (decode(pair), String::new()),
+        })
+        .collect()
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
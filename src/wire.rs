@@ -0,0 +1,429 @@
+use std::io::{self, BufRead, Read};
+
+use crate::chunked::ChunkedDecoder;
+use crate::errors::{Error, ResponseError};
+use crate::headers::HeaderMap;
+use crate::status_code::StatusCode;
+
+/// The largest response head (status line and headers) that will be read
+const MAX_HEAD_LENGTH: usize = 64 * 1024;
+
+/// How the end of a response body is found (RFC 9112, section 6.3)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Framing {
+    /// The response has no body (HEAD requests, 1xx, 204 and 304 responses)
+    Empty,
+    /// The body is exactly this many bytes long
+    Length(u64),
+    /// The body uses chunked transfer coding
+    Chunked,
+    /// The body ends when the server closes the connection
+    Close,
+}
+
+impl Framing {
+    /// Work out the framing of a response body from the request method, the status code and the response headers
+    pub(crate) fn of(method: &str, status_code: u16, headers: &HeaderMap) -> Result<Framing, String> {
+        if method.eq_ignore_ascii_case("HEAD") || (100..200).contains(&status_code) || status_code == 204 || status_code == 304 {
+            return Ok(Framing::Empty);
+        }
+
+        // Transfer-Encoding overrides Content-Length; if chunked is not the final coding, the body is close-delimited
+        if let Some(transfer_encoding) = headers.joined("Transfer-Encoding") {
+            let last = transfer_encoding.rsplit(',').next().unwrap_or("").trim();
+            return if last.eq_ignore_ascii_case("chunked") {
+                Ok(Framing::Chunked)
+            } else {
+                Ok(Framing::Close)
+            };
+        }
+
+        if let Some(content_length) = headers.joined("Content-Length") {
+            // a list of identical values (from duplicated headers) is allowed, differing values are not
+            let mut lengths = content_length.split(',').map(|length| length.trim().parse::<u64>());
+
+            let length = match lengths.next() {
+                Some(Ok(length)) => length,
+                _ => return Err(format!("invalid Content-Length: {}", content_length)),
+            };
+            if !lengths.all(|other| other == Ok(length)) {
+                return Err(format!("invalid Content-Length: {}", content_length));
+            }
+
+            return Ok(Framing::Length(length));
+        }
+
+        Ok(Framing::Close)
+    }
+}
+
+/// Finds the end of a response head in the bytes read from a connection, without doing any I/O itself
+///
+/// This lets the blocking and the async code read response heads the same way.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HeadDecoder {
+    head: Vec<u8>,
+    line_start: usize,
+    done: bool,
+}
+
+impl HeadDecoder {
+    pub(crate) fn new() -> HeadDecoder {
+        HeadDecoder::default()
+    }
+
+    /// Whether the whole head has been decoded
+    pub(crate) fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Decode as much of `input` as belongs to the head, returning how many bytes were used
+    ///
+    /// Empty input means the connection was closed. The bytes after the head are left for the body.
+    pub(crate) fn decode(&mut self, input: &[u8]) -> io::Result<usize> {
+        if input.is_empty() && !self.done {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before the response head was complete",
+            ));
+        }
+
+        let mut consumed = 0;
+
+        while !self.done && consumed < input.len() {
+            let rest = &input[consumed..];
+            let (line, complete) = match rest.iter().position(|&byte| byte == b'\n') {
+                Some(newline) => (&rest[..=newline], true),
+                None => (rest, false),
+            };
+
+            self.head.extend_from_slice(line);
+            consumed += line.len();
+
+            if self.head.len() > MAX_HEAD_LENGTH {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "response head is too large"));
+            }
+            if !complete {
+                break;
+            }
+
+            let start = self.line_start;
+            let line = &self.head[start..];
+            if line == b"\r\n" || line == b"\n" {
+                // a stray empty line before the status line is ignored (RFC 9112, section 2.2)
+                if start == 0 {
+                    self.head.clear();
+                    continue;
+                }
+                self.head.truncate(start);
+                while self.head.last().is_some_and(|&byte| byte == b'\r' || byte == b'\n') {
+                    self.head.pop();
+                }
+                self.done = true;
+            } else {
+                self.line_start = self.head.len();
+            }
+        }
+
+        Ok(consumed)
+    }
+
+    /// The decoded head, without the final CRLF CRLF
+    pub(crate) fn into_head(self) -> Vec<u8> {
+        self.head
+    }
+}
+
+/// Read a response head, up to and including the empty line that ends it
+///
+/// The returned bytes do not include the final CRLF CRLF.
+pub(crate) fn read_head<R: BufRead>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut decoder = HeadDecoder::new();
+
+    while !decoder.is_done() {
+        let input = reader.fill_buf()?;
+        let consumed = decoder.decode(input)?;
+        reader.consume(consumed);
+    }
+
+    Ok(decoder.into_head())
+}
+
+/// A parsed response head
+#[derive(Clone, Debug)]
+pub(crate) struct Head {
+    /// The HTTP version from the status line (example: `1.1`)
+    pub(crate) version: String,
+    /// The status code, as sent by the server
+    pub(crate) code: u16,
+    /// The status code, with codes that are not known mapped to the x00 code of their class
+    pub(crate) status_code: StatusCode,
+    /// The header fields, in the order they were received
+    pub(crate) headers: HeaderMap,
+}
+
+fn invalid(message: String) -> ResponseError {
+    ResponseError::new(message)
+}
+
+/// Parse a response head (as returned by [`read_head`]) into its status line and header fields (RFC 9112, sections 4 and 5)
+pub(crate) fn parse_head(head: &[u8]) -> Result<Head, ResponseError> {
+    let mut lines = head.split(|&byte| byte == b'\n').map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+
+    let status_line = lines.next().unwrap_or_default();
+    let (version, code) = parse_status_line(status_line)?;
+
+    let status_code = match StatusCode::from_u16(code) {
+        Some(status_code) => status_code,
+        // an unrecognised status code is treated as the x00 code of its class (RFC 9110, section 15)
+        None => match StatusCode::from_u16(code / 100 * 100) {
+            Some(status_code) => status_code,
+            None => return Err(invalid(format!("invalid status code: {}", code))),
+        },
+    };
+
+    let mut headers = HeaderMap::new();
+
+    for line in lines {
+        if line.first().is_some_and(|&byte| byte == b' ' || byte == b'\t') {
+            return Err(invalid(format!(
+                "obsolete line folding is not supported: {:?}",
+                String::from_utf8_lossy(line)
+            )));
+        }
+
+        let (name, value) = parse_field(line)?;
+        headers.append(name, value);
+    }
+
+    Ok(Head {
+        version,
+        code,
+        status_code,
+        headers,
+    })
+}
+
+/// Parse a status line (example: `HTTP/1.1 404 Not Found`) into the HTTP version and status code
+fn parse_status_line(line: &[u8]) -> Result<(String, u16), ResponseError> {
+    let malformed = || invalid(format!("malformed status line: {:?}", String::from_utf8_lossy(line)));
+
+    let mut parts = line.splitn(3, |&byte| byte == b' ');
+    let version = parts.next().unwrap_or_default();
+    let code = parts.next().ok_or_else(malformed)?;
+
+    let version = match version {
+        [b'H', b'T', b'T', b'P', b'/', major, b'.', minor] if major.is_ascii_digit() && minor.is_ascii_digit() => {
+            format!("{}.{}", *major as char, *minor as char)
+        }
+        _ => return Err(malformed()),
+    };
+
+    if code.len() != 3 || !code.iter().all(u8::is_ascii_digit) {
+        return Err(malformed());
+    }
+    let code = code.iter().fold(0, |code, digit| code * 10 + u16::from(digit - b'0'));
+
+    Ok((version, code))
+}
+
+/// Parse a header field line (example: `Content-Type: text/html`) into its name and value
+fn parse_field(line: &[u8]) -> Result<(String, String), ResponseError> {
+    let malformed = |reason: &str| invalid(format!("{}: {:?}", reason, String::from_utf8_lossy(line)));
+
+    let colon = match line.iter().position(|&byte| byte == b':') {
+        Some(colon) => colon,
+        None => return Err(malformed("header field is missing a colon")),
+    };
+
+    let name = &line[..colon];
+    if name.is_empty() || !name.iter().all(|&byte| is_token_char(byte)) {
+        return Err(malformed("invalid header field name"));
+    }
+
+    let value = &line[colon + 1..];
+    if value.iter().any(|&byte| byte == b'\r' || byte == 0) {
+        return Err(malformed("invalid header field value"));
+    }
+
+    let value = String::from_utf8_lossy(value);
+    let value = value.trim_matches(|c| c == ' ' || c == '\t');
+
+    Ok((String::from_utf8_lossy(name).into_owned(), value.to_string()))
+}
+
+/// Whether a byte can be part of a token, such as a header field name (RFC 9110, section 5.6.2)
+fn is_token_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// A response body once it has been read
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Body {
+    /// The body, with any chunked framing removed
+    pub(crate) decoded: Vec<u8>,
+    /// The body exactly as it was received
+    pub(crate) raw: Vec<u8>,
+    /// The trailer fields of a chunked body
+    pub(crate) trailers: HeaderMap,
+}
+
+/// Decodes a response body according to its framing, from the bytes read from a connection, without doing any I/O itself
+///
+/// This never uses bytes past the end of the body, so the connection underneath can be used for another request
+/// afterwards.
+#[derive(Debug)]
+pub(crate) struct BodyDecoder {
+    framing: Framing,
+    remaining: u64,
+    chunked: ChunkedDecoder,
+    done: bool,
+}
+
+impl BodyDecoder {
+    pub(crate) fn new(framing: Framing) -> BodyDecoder {
+        BodyDecoder {
+            framing,
+            remaining: match framing {
+                Framing::Length(length) => length,
+                _ => 0,
+            },
+            chunked: ChunkedDecoder::new(),
+            done: framing == Framing::Empty || framing == Framing::Length(0),
+        }
+    }
+
+    /// Whether the whole body has been decoded
+    pub(crate) fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// The trailer fields of a chunked body, available once the body has been decoded
+    pub(crate) fn trailers(&self) -> HeaderMap {
+        self.chunked.trailers().clone()
+    }
+
+    /// Decode as much of `input` as belongs to the body into `out`, returning how many bytes were used
+    ///
+    /// Empty input means the connection was closed, which ends a close-delimited body and truncates any other.
+    pub(crate) fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> io::Result<usize> {
+        if self.done {
+            return Ok(0);
+        }
+
+        match self.framing {
+            Framing::Empty => Ok(0),
+            Framing::Length(_) => {
+                if input.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("body ended {} bytes before its Content-Length", self.remaining),
+                    ));
+                }
+
+                let read = self.remaining.min(input.len() as u64) as usize;
+                out.extend_from_slice(&input[..read]);
+
+                self.remaining -= read as u64;
+                self.done = self.remaining == 0;
+                Ok(read)
+            }
+            Framing::Chunked => {
+                if input.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "chunked body ended before the last chunk",
+                    ));
+                }
+
+                let consumed = self
+                    .chunked
+                    .decode(input, out)
+                    .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+
+                self.done = self.chunked.is_done();
+                Ok(consumed)
+            }
+            Framing::Close => {
+                self.done = input.is_empty();
+                out.extend_from_slice(input);
+                Ok(input.len())
+            }
+        }
+    }
+}
+
+/// Reads a response body according to its framing, stopping at the end of the body
+///
+/// This never reads past the end of the body, so the connection underneath can be used for another request afterwards.
+pub(crate) struct BodyReader<R> {
+    reader: R,
+    decoder: BodyDecoder,
+    decoded: Vec<u8>,
+    decoded_pos: usize,
+    raw: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> BodyReader<R> {
+    pub(crate) fn new(reader: R, framing: Framing) -> BodyReader<R> {
+        BodyReader {
+            reader,
+            decoder: BodyDecoder::new(framing),
+            decoded: Vec::new(),
+            decoded_pos: 0,
+            raw: None,
+        }
+    }
+
+    /// Keep a copy of the body bytes exactly as they were received, including any chunk framing
+    pub(crate) fn record_raw(&mut self) {
+        self.raw = Some(Vec::new());
+    }
+
+    /// The body bytes as they were received, if [`BodyReader::record_raw`] was called
+    pub(crate) fn take_raw(&mut self) -> Vec<u8> {
+        self.raw.take().unwrap_or_default()
+    }
+
+    /// The trailer fields of a chunked body, available once the body has been read to the end
+    pub(crate) fn trailers(&self) -> HeaderMap {
+        self.decoder.trailers()
+    }
+
+    /// The reader the body is read from
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+}
+
+impl<R: BufRead> Read for BodyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.decoded_pos == self.decoded.len() {
+            if self.decoder.is_done() {
+                return Ok(0);
+            }
+
+            self.decoded.clear();
+            self.decoded_pos = 0;
+
+            let input = self.reader.fill_buf()?;
+            let consumed = self.decoder.decode(input, &mut self.decoded)?;
+
+            if let Some(raw) = self.raw.as_mut() {
+                raw.extend_from_slice(&input[..consumed]);
+            }
+            self.reader.consume(consumed);
+        }
+
+        let available = &self.decoded[self.decoded_pos..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.decoded_pos += read;
+
+        Ok(read)
+    }
+}